@@ -1,10 +1,18 @@
-use clap::{ArgGroup, Parser};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+use clap::{Arg, ArgGroup, Command, Parser};
 use clap_complete::Shell;
 
+use crate::info::Format;
+use crate::search::normalize::Case;
+use crate::search::scope::Scope;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)] // Populates author/version info from Cargo.toml
 #[command(group(ArgGroup::new("AlternateOperatingModes")
-        .args(&["list", "langs", "set_conf", "get_conf",  "gen_completions", "check_remote", "sync", "reclone"])
+        .args(&["list", "langs", "search", "set_conf", "get_conf",  "gen_completions", "check_remote", "sync", "reclone"])
         .multiple(false)))]
 #[clap(
     name = "xiny",
@@ -12,19 +20,29 @@ use clap_complete::Shell;
     about = "A CLI for the LearnXinYMinutes repository."
 )]
 #[command(group(ArgGroup::new("any_subject").args(&["explicit_subject", "implicit_subject"]).multiple(false).conflicts_with("AlternateOperatingModes")))]
+#[command(group(ArgGroup::new("Searches").args(&["find", "search", "symbol"]).multiple(false)))]
+#[command(group(ArgGroup::new("Formatted").args(&["info", "toc"]).multiple(false)))]
+#[command(group(ArgGroup::new("Extraction").args(&["code", "code_out"]).multiple(true)))]
 #[command(group(ArgGroup::new("LangRequirements").args(&["explicit_subject", "implicit_subject"]).multiple(false)))]
 pub struct CliArgs {
     // IMPLICIT SUBJECT
     // ================================================================================================================
     #[arg(
-        required_unless_present_any(&["AlternateOperatingModes", "explicit_subject"]),
-        help = "The subject to view (e.g. bash, python, etc.)",
-        value_parser = SUBJECTS,
+        required_unless_present_any(&["AlternateOperatingModes", "explicit_subject", "symbol"]),
+        help = "The subject to view (e.g. bash, python, etc.), optionally followed by a question (see --ask)",
+        value_parser = SubjectParser,
         hide_possible_values(true),
         index(1),
     )]
     pub implicit_subject: Option<String>,
 
+    // IMPLICIT QUESTION
+    // ================================================================================================================
+    // The rest of a question asked as subject/question, e.g. `xiny python/reverse a list`,
+    // so that it doesn't have to be quoted.
+    #[arg(index(2), num_args(0..), requires("implicit_subject"), hide(true))]
+    pub implicit_question: Vec<String>,
+
     // EXPLICIT SUBJECT
     // ================================================================================================================
     #[arg(
@@ -78,56 +96,243 @@ languages.",
         requires_if("find", "implicit_subject"),
         num_args(1..),
         value_name = "TERMS",
-        help = "[NOT YET IMPLEMENTED] Searches the subject document for the provided terms and displays surrounding lines (see --help)",
-       long_help = "[NOT YET IMPLEMENTED] Searches the subject document for the provided terms and displays lines surrounding the
-match. The default behavior is quite basic; the document is searched line by line, and
-the line which contains the most search terms is selected as the match. The behavior can
-be configured using:
+        help = "Searches the subject document for the provided terms and displays surrounding lines (see --help)",
+       long_help = "Searches the subject document for the provided terms and displays lines surrounding the
+match. The document is searched line by line, and a line matches if it contains every
+term, with lines containing them most often ranked first. The terms form a small query
+language, which --search and --interactive understand as well:
+
+    a b                  Lines containing both a and b
+    a | b                Lines containing a or b; binds tighter than a space
+    -a                   Lines not containing a
+    \"a b\"                The phrase a b, as a single term
+    code:a heading:a     Only code block or heading lines containing a
+    subject:go lang:de   Only documents of the subject, or in the language
+
+Quote the query if it excludes terms, e.g. --find 'list -reverse', as -reverse would be
+taken for a flag otherwise.
+
+The behavior can be configured using:
 
      Flags               Options
 
-    --interactive        --context (default: 6)
-    --vimgrep            --matches (default: 1)
-    --regex              --case    (default: insensitive)
-    --fuzzy"
+    --interactive        --context (default: the block of the match)
+    --vimgrep            --before-context, --after-context
+    --regex              --matches (default: 1)
+    --fuzzy              --case    (default: insensitive)
+    --stem               --near
+    --fold-diacritics"
     )]
     pub find: Option<Vec<String>>,
 
+    // ASK
+    // ================================================================================================================
+    #[arg(
+        long,
+        num_args(1..),
+        conflicts_with_all(&["find", "search", "interactive", "where", "vimgrep"]),
+        value_name = "QUESTION",
+        help = "Prints the section of the subject document that best answers the question (see --help)",
+        long_help = "Prints the section of the subject document that best answers the question, e.g.
+`xiny python --ask reverse a list`, or in short, `xiny python/reverse a list`. The document
+is split into sections at every heading, and the sections are ranked by TF-IDF over the
+words of the question; words occurring in fewer sections weigh more, and hits inside of
+code blocks weigh more than hits in prose. Use --matches to print more than one section,
+and --regex or --fuzzy to change how the words are matched."
+    )]
+    pub ask: Option<Vec<String>>,
+
+    // SEARCH
+    // ================================================================================================================
+    #[arg(
+        long,
+        short = 'S',
+        num_args(1..),
+        conflicts_with_all(&["any_subject", "find", "interactive"]),
+        value_name = "TERMS",
+        help = "Searches every subject in the current language for the provided terms (see --help)",
+        long_help = "Searches every subject available in the current language (see --lang) for the provided
+terms, for when you know the concept, but not which subject covers it. Every matching line
+is printed as subject:line: text, grouped by subject along with how many hits each subject
+had. The terms are a query, as described for --find; if it uses the lang: field, then every
+language is searched, rather than only the current one. --regex and --fuzzy select the
+other search engines, just like they do for --find."
+    )]
+    pub search: Option<Vec<String>>,
+
+    // SYMBOL
+    // ================================================================================================================
+    #[arg(
+        long,
+        num_args(1),
+        conflicts_with_all(&["ask", "interactive", "near", "stem", "regex", "fuzzy"]),
+        value_name = "NAME",
+        help = "Lists where an identifier occurs in the code of every subject, or of the given one (see --help)",
+        long_help = "Lists every line of code that the identifier occurs on, in every subject available in the
+current language (see --lang), or only in the subject given alongside it, e.g.
+--symbol defer go. Unlike --search, only code blocks are looked at, and the name has to
+match an identifier exactly, case included, as the language of the code block's fence
+tag spells them; comments and strings don't count. Qualified names, such as fmt.Println
+or std::mem::swap, may be given as well. The output is that of --search."
+    )]
+    pub symbol: Option<String>,
+
+    // SCOPE
+    // ================================================================================================================
+    #[arg(
+        long = "in",
+        value_enum,
+        default_value_t = Scope::All,
+        value_name = "SCOPE",
+        help = "Restrict --find, --search and --interactive to lines of code blocks, prose, or headings.",
+        long_help = "Restrict --find, --search and --interactive to certain lines of the document, based on
+which Markdown block they belong to. The front matter is only searched with 'all'.
+
+    all         Every line of the document (default)
+    code        Only lines inside of fenced code blocks, i.e. the syntax
+    prose       Only the explanatory text outside of code blocks and headings
+    headings    Only heading lines"
+    )]
+    pub scope: Scope,
+
+    // CASE
+    // ================================================================================================================
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = Case::Insensitive,
+        value_name = "CASE",
+        help = "Whether searches are case sensitive: insensitive (default), sensitive, or smart (see --help)",
+        long_help = "Whether --find, --search, --ask and --interactive are case sensitive. Case insensitive
+searches fold the case of all of Unicode, not just ASCII, so e.g. ПРИВЕТ matches привет,
+and STRASSE matches Straße. They also match compatibility equivalents, such as the ligature
+ﬁ and fi. With 'smart', every term is case insensitive, unless it contains an uppercase
+character. Text is always normalized, so accented characters match however they're encoded."
+    )]
+    pub case: Case,
+
+    // FOLD DIACRITICS
+    // ================================================================================================================
+    #[arg(
+        long,
+        help = "Ignore accents and other diacritics when searching, so that e.g. cafe matches café."
+    )]
+    pub fold_diacritics: bool,
+
+    // STEM
+    // ================================================================================================================
+    #[arg(
+        long,
+        conflicts_with_all(&["regex", "fuzzy"]),
+        help = "Also match other inflections of words, e.g. iterating matches iterates (see --help)",
+        long_help = "Also match other inflections of the words of --find, --search, --ask and --interactive,
+e.g. iterating matches iterate and iterates, and Listen matches Liste. Words are reduced to
+their stems by the rules of the language of the document being searched, which are available
+for Arabic, Danish, Dutch, English, Finnish, French, German, Greek, Hungarian, Italian,
+Norwegian, Portuguese, Romanian, Russian, Spanish, Swedish, Tamil and Turkish. In other
+languages, words are only matched as they are. Not supported by --regex and --fuzzy."
+    )]
+    pub stem: bool,
+
+    // VIMGREP
+    // ================================================================================================================
+    #[arg(
+        long,
+        requires("Searches"),
+        conflicts_with_all(&["interactive", "context", "matches"]),
+        help = "Output every --find, --search or --symbol match as path:line:col:text for editor quickfix lists.",
+        long_help = "Output every --find, --search or --symbol match as path:line:col:text, the same format as ripgrep's
+--vimgrep, using the path of the document in the local repository. Every match is printed,
+in document order, so the output can be fed straight to Vim/Neovim's :cexpr or Emacs'
+grep-mode to jump into the documentation."
+    )]
+    pub vimgrep: bool,
+
     // CONTEXT
     // ================================================================================================================
     #[arg(
         long,
         short = 'C',
-        requires("find"),
-        conflicts_with("interactive"),
-        default_value = "6",
         num_args(1),
         value_name = "LINES",
-        help = "[NOT YET IMPLEMENTED] The number of lines to display before and after a --find match.",
-        long_help = "[NOT YET IMPLEMENTED] The number of lines to display before and after a --find match. To clarify, a value of 6
-means you'll see 13 lines. That's 6 before the match, 6 after the match, plus the match
-itself; 6+6+1 = 13. This does not mean the total line count but how many lines before and
-ater the match. That means that the line number must be divisible by 2. You cannot have 5
-lines before and 6 lines after. The context is always centered around the match. Passing
-3 would not work, but 4 would. If you pass 0, then the entire document is displayed, the
-only difference being the match is highlighted."
+        help = "The number of lines to display before and after a --find match, instead of its block (see --help)",
+        long_help = "The number of lines to display before and after a --find match, or the match selected in
+--interactive. A value of 6 means you'll see 13 lines; 6 before the match, the match itself,
+and 6 after it. Use --before-context and --after-context to set either side on its own. If
+you pass 0, then the entire document is displayed, the only difference being the match is
+highlighted.
+
+Without any of these, the context is the Markdown block the match is in: the whole code
+block, fences included, when the match is in code, or the paragraph when it's in prose, and
+a heading along with the block below it. Long blocks are cut down to the 30 lines around the
+match."
     )]
     pub context: Option<usize>,
 
+    // BEFORE CONTEXT
+    // ================================================================================================================
+    #[arg(
+        long,
+        short = 'B',
+        num_args(1),
+        value_name = "LINES",
+        help = "The number of lines to display before a --find match, overriding --context."
+    )]
+    pub before_context: Option<usize>,
+
+    // AFTER CONTEXT
+    // ================================================================================================================
+    #[arg(
+        long,
+        short = 'A',
+        num_args(1),
+        value_name = "LINES",
+        help = "The number of lines to display after a --find match, overriding --context."
+    )]
+    pub after_context: Option<usize>,
+
     // MATCHES
     // ================================================================================================================
     #[arg(
         long,
         short,
-        requires("find"),
         conflicts_with("interactive"),
         default_value = "1",
         num_args(1),
         value_name = "AMOUNT",
-        help = "[NOT YET IMPLEMENTED] The number of --find matches to display. If set to 0, all matches are displayed."
+        help = "The number of --find matches, or --ask sections to display. If set to 0, all of them are displayed."
     )]
     pub matches: Option<usize>,
 
+    // NEAR
+    // ================================================================================================================
+    #[arg(
+        long,
+        conflicts_with("ask"),
+        num_args(1),
+        value_name = "LINES",
+        help = "Match the query within any window of up to LINES lines, rather than a single line (see --help)",
+        long_help = "Match the query within any window of up to LINES consecutive lines, rather than within a
+single line, for --find, --search and --interactive. Terms are then found even when they're
+spread across a comment and the code below it, e.g. --find 'reverse list' --near 3. Every
+window is reported as a single match spanning its lines, the smallest window that satisfies
+the query, and tighter windows are ranked above looser ones. Excluded terms may not occur
+anywhere within the window."
+    )]
+    pub near: Option<usize>,
+
+    // COLLAPSE
+    // ================================================================================================================
+    #[arg(
+        long,
+        requires("Searches"),
+        help = "Only show the best --find, --search or --symbol match of every section of a document.",
+        long_help = "Only show the best --find, --search or --symbol match of every section of a document, where a section
+runs from a heading up to the next one, whatever their levels. Long documents often repeat
+a term all over a section, and this leaves one match to jump to per section instead."
+    )]
+    pub collapse: bool,
+
     // INTERACTIVE
     // ================================================================================================================
     #[arg(
@@ -136,7 +341,19 @@ only difference being the match is highlighted."
         requires_if("interactive", "explicit_subject"),
         requires_if("interactive", "implicit_subject"),
         conflicts_with("find"),
-        help = "[EXPERIMENTAL / INCOMPLETE] An interactive version of find; type, see and select matches interactively in a TUI popup (see --help)"
+        help = "[EXPERIMENTAL / INCOMPLETE] An interactive version of find; type, see and select matches interactively in a TUI popup (see --help)",
+        long_help = "[EXPERIMENTAL / INCOMPLETE] An interactive version of find; type, see and select matches
+interactively in a TUI popup. The query is edited with vi keys, and besides them:
+
+    Tab                  Switch to the next search engine
+    Enter                Submit the query, adding it to the subject's history
+    Up/Down, k/j         Recall older or newer queries from the history
+    /                    Search the history; Up/Down for more, Enter to keep, Esc to give up
+    n, N                 Select the next or previous match
+    q                    Quit
+
+The history keeps the last 200 distinct queries of every subject in the local data
+directory."
     )]
     pub interactive: bool,
 
@@ -148,15 +365,18 @@ only difference being the match is highlighted."
         requires_if("regex", "find"),
         requires_if("regex", "interactive"),
         conflicts_with("fuzzy"),
-        help = "[NOT YET IMPLEMENTED] Enable Regex mode for --find; all terms will be treated as regular expressions (logical AND chain) --help for more info.",
-        long_help = "Enable Regex mode for --find; all terms will be treated as regular expressions. Each
-expression will be attempted on each line, and if all expressions pass, then the line is
-added to the list of matches; a logical AND across all expressions. When several lines
-pass the same expression(s), then the relevancy is determined by the length of the
-matched content. Shorter content is treated as more relevant. The logic being that the
-excess content does not need to exist for any match to be able to exist. The shorter
-content matched the same expression more tightly. If fuzzy imprecise matching is what you
-need, then --fuzzy may be a better fit."
+        help = "Enable Regex mode for --find or --interactive; all terms will be treated as regular expressions (logical AND chain) --help for more info.",
+        long_help = "Enable Regex mode for --find or --interactive; all terms will be treated as regular
+expressions, matched case insensitively. Each expression will be attempted on each line,
+and if all expressions pass, then the line is added to the list of matches; a logical AND
+across all expressions. When several lines pass the same expression(s), then the relevancy
+is determined by the length of the matched content. Shorter content is treated as more
+relevant. The logic being that the excess content does not need to exist for any match to
+be able to exist. The shorter content matched the same expression more tightly. Terms are
+separated by whitespace, so use \\s or a \"quoted phrase\" to match whitespace within an
+expression; the rest of the query language (see --find) applies as well, and a | is only
+an operator when it stands on its own. If fuzzy imprecise matching is what you need, then
+--fuzzy may be a better fit."
     )]
     pub regex: bool,
 
@@ -168,10 +388,31 @@ need, then --fuzzy may be a better fit."
         requires_if("fuzzy", "find"),
         requires_if("fuzzy", "interactive"),
         conflicts_with("regex"),
-        help = "[NOT YET IMPLEMENTED] Enable FuzzyFind mode for --find; terms will be concatenated into one single query, and matches are derived from similarity."
+        help = "Enable FuzzyFind mode for --find or --interactive; terms are matched as subsequences, and matches are derived from similarity.",
+        long_help = "Enable FuzzyFind mode for --find or --interactive; every term is matched against every
+line as a case insensitive subsequence, the same way fzf does. Lines are ranked by how well
+the terms align with them; matches at the start of words, on camelCase humps, and
+consecutive runs of characters score higher than characters scattered across the line."
     )]
     pub fuzzy: bool,
 
+    // EXTERNAL
+    // ================================================================================================================
+    #[arg(
+        long,
+        conflicts_with_all(&["regex", "fuzzy", "stem"]),
+        help = "Delegate matching to an external command such as ripgrep (see --help)",
+        long_help = "Delegate matching the terms of --find, --search, --ask and --interactive to an external
+command, set by the search_command config key, which is `rg --json --fixed-strings --regexp`
+by default. For every term and document, the command is run with the term as its last
+argument and the lines of the document on its stdin. It has to print the lines that match,
+either as ripgrep's --json messages, or as line:text the way grep -n does, and exit with 0
+or 1 like grep; e.g. `grep -n -F -e` works too. Terms match the same way they do without
+this flag. If the command can't be found, the built-in matching is used instead. Setting
+the engine config key to external makes this the default."
+    )]
+    pub external: bool,
+
     // FUZZY
     // ================================================================================================================
     #[arg(
@@ -223,6 +464,95 @@ need, then --fuzzy may be a better fit."
     )]
     pub r#where: bool,
 
+    // INFO
+    // ================================================================================================================
+    #[arg(
+        long,
+        requires_if("info", "explicit_subject"),
+        requires_if("info", "implicit_subject"),
+        conflicts_with_all(&["find", "ask", "interactive", "where", "symbol"]),
+        help = "Print what the document's front matter says about the subject, and its translations (see --help)",
+        long_help = "Print what the front matter of the subject's document says about it; its display name,
+category, contributors, translators and the file its code is meant to be saved as, along
+with where the document is, how many lines it has, and every language it's available in.
+The document is the one in the language set by --lang. Use --format json for scripts."
+    )]
+    pub info: bool,
+
+    // TOC
+    // ================================================================================================================
+    #[arg(
+        long,
+        requires_if("toc", "explicit_subject"),
+        requires_if("toc", "implicit_subject"),
+        conflicts_with_all(&["find", "ask", "interactive", "where", "symbol"]),
+        help = "Print the headings of the document, indented by level, with their line numbers."
+    )]
+    pub toc: bool,
+
+    // SECTION
+    // ================================================================================================================
+    #[arg(
+        long,
+        value_name = "HEADING",
+        requires_if("section", "explicit_subject"),
+        requires_if("section", "implicit_subject"),
+        conflicts_with_all(&["find", "ask", "interactive", "where", "symbol", "Formatted"]),
+        help = "Only show the section of the document under the heading (see --help)",
+        long_help = "Only show the section of the document under the heading, up to the next heading of the
+same or a higher level, through the configured renderer. The heading is looked up by its
+text, ignoring case, then by its anchor, then as containing the given text, and lastly
+fuzzily, so `--section goroutine` finds Goroutines. The anchor can also follow the
+subject, e.g. `xiny go#goroutines`. See --toc for the headings of a document."
+    )]
+    pub section: Option<String>,
+
+    // CODE
+    // ================================================================================================================
+    #[arg(
+        long,
+        requires_if("code", "explicit_subject"),
+        requires_if("code", "implicit_subject"),
+        conflicts_with_all(&["find", "ask", "interactive", "where", "symbol", "Formatted"]),
+        help = "Only print the code of the fenced code blocks of the document (see --help)",
+        long_help = "Only print the code of the fenced code blocks of the document, without their fences or
+any of the prose around them, with a blank line between blocks. --section restricts it to
+the blocks of a section, and --block to a single block. Use --code-out to save the code
+to a file instead."
+    )]
+    pub code: bool,
+
+    // BLOCK
+    // ================================================================================================================
+    #[arg(
+        long,
+        value_name = "N",
+        requires("Extraction"),
+        help = "Only extract the Nth code block, counting from 1, of the document or of --section."
+    )]
+    pub block: Option<usize>,
+
+    // CODE-OUT
+    // ================================================================================================================
+    #[arg(
+        long = "code-out",
+        value_name = "DIR",
+        conflicts_with_all(&["find", "ask", "interactive", "where", "symbol", "Formatted"]),
+        help = "Save the code of --code to a file in DIR, named after the document's filename, e.g. learnpython.py"
+    )]
+    pub code_out: Option<PathBuf>,
+
+    // FORMAT
+    // ================================================================================================================
+    #[arg(
+        long,
+        value_enum,
+        requires("Formatted"),
+        value_name = "FORMAT",
+        help = "How --info and --toc print their output: text for people, or json for scripts."
+    )]
+    pub format: Option<Format>,
+
     // GET-CONF
     // ================================================================================================================
     #[arg(
@@ -245,6 +575,41 @@ need, then --fuzzy may be a better fit."
     pub set_conf: Option<Vec<String>>,
 }
 
+/// Parses a subject, optionally followed by a slash and the start of a
+/// question, e.g. python/reverse, or by the anchor of a section, e.g.
+/// go#goroutines. Only the subject is validated, and the subjects are still
+/// offered as completions.
+#[derive(Debug, Clone)]
+struct SubjectParser;
+
+impl TypedValueParser for SubjectParser {
+    type Value = String;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, clap::Error> {
+        let subjects = PossibleValuesParser::new(SUBJECTS);
+
+        let Some(value) = value.to_str() else {
+            return subjects.parse_ref(cmd, arg, value);
+        };
+
+        let Some(split) = value.find(['/', '#']) else {
+            return subjects.parse_ref(cmd, arg, OsStr::new(value));
+        };
+
+        subjects.parse_ref(cmd, arg, OsStr::new(&value[..split]))?;
+        Ok(value.to_string())
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(SUBJECTS.iter().map(PossibleValue::new)))
+    }
+}
+
 const LANGUAGES: [&str; 38] = [
     "ar-ar", "be-by", "bg-bg", "ca-es", "cs-cz", "de-de", "el-gr", "en-us", "es-es", "fa-ir",
     "fi-fi", "fr-fr", "he-he", "hi-in", "hu-hu", "id-id", "it-it", "ja-jp", "ko-kr", "lt-lt",
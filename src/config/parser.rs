@@ -5,6 +5,9 @@ use std::path::PathBuf;
 use anyhow::{self as ah, Context};
 use dirs;
 
+use crate::search::engines::external::DEFAULT_COMMAND;
+use crate::search::engines::EngineKind;
+
 #[derive(Debug, Clone)]
 pub struct ConfigFile {
     pub values: Config,
@@ -88,6 +91,13 @@ pub struct Config {
     pub langs: Vec<String>,
     pub renderer: String,
     pub first: bool,
+
+    /// The search engine used when no flag picks one; terms, regex, fuzzy or
+    /// external.
+    pub engine: String,
+
+    /// The command the external search engine runs, e.g. rg --json.
+    pub search_command: String,
 }
 
 impl Default for Config {
@@ -98,6 +108,8 @@ impl Default for Config {
             langs: vec![],
             renderer: "glow".into(),
             first: true,
+            engine: "terms".into(),
+            search_command: DEFAULT_COMMAND.into(),
         }
     }
 }
@@ -108,7 +120,10 @@ impl Config {
     }
 
     pub fn is_valid_key(key: &str) -> bool {
-        matches!(key, "repo" | "branch" | "langs" | "renderer" | "first")
+        matches!(
+            key,
+            "repo" | "branch" | "langs" | "renderer" | "first" | "engine" | "search_command"
+        )
     }
 
     pub fn set_value(&mut self, key: &str, value: &str) -> ah::Result<()> {
@@ -118,6 +133,8 @@ impl Config {
             "langs" => self.langs = value.split(',').map(|s| s.into()).collect(),
             "renderer" => self.renderer = value.into(),
             "first" if matches!(key, "true" | "false") => self.first = value.parse().unwrap(),
+            "engine" if EngineKind::from_name(value).is_some() => self.engine = value.into(),
+            "search_command" => self.search_command = value.into(),
             _ => ah::bail!("Invalid config assignment {} = {}", key, value),
         };
 
@@ -131,6 +148,8 @@ impl Config {
             "langs" => Some(self.langs.join(",")),
             "renderer" => Some(self.renderer.clone()),
             "first" => Some(self.first.to_string()),
+            "engine" => Some(self.engine.clone()),
+            "search_command" => Some(self.search_command.clone()),
             _ => None,
         }
     }
@@ -156,6 +175,8 @@ impl Config {
                 "langs" => template.langs = value.split(',').map(|s| s.into()).collect(),
                 "renderer" => template.renderer = value.into(),
                 "first" => template.first = value.parse().unwrap(),
+                "engine" => template.engine = value.into(),
+                "search_command" => template.search_command = value.into(),
                 _ => eprintln!("Unknown config key: {}", key),
             }
         }
@@ -171,6 +192,8 @@ impl Config {
         config.push_str(&format!("langs: {}\n", self.langs.join(",")));
         config.push_str(&format!("renderer: {}\n", self.renderer));
         config.push_str(&format!("first: {}\n", self.first));
+        config.push_str(&format!("engine: {}\n", self.engine));
+        config.push_str(&format!("search_command: {}\n", self.search_command));
 
         config
     }
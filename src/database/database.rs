@@ -1,9 +1,11 @@
 use std::{
     collections::{BTreeMap as Map, HashSet},
     ffi::OsStr,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
+use crate::database::document::Document;
 use crate::language::language::Language;
 use anyhow::{self as ah, Context};
 
@@ -12,6 +14,9 @@ pub type SubjectName = String;
 #[derive(Debug, Clone, Default)]
 pub struct SubjectFiles {
     pub files: Map<Language, PathBuf>,
+
+    /// The documents parsed so far, by language; shared between clones.
+    documents: Arc<Mutex<Map<Language, Arc<Document>>>>,
 }
 
 impl SubjectFiles {
@@ -26,6 +31,27 @@ impl SubjectFiles {
     pub fn add_translation(&mut self, lang: Language, path: PathBuf) {
         self.files.insert(lang, path);
     }
+
+    /// The document in the language, parsed the first time it's asked for.
+    pub fn document(&self, lang: &Language) -> ah::Result<Arc<Document>> {
+        let path = self
+            .get_in_language(lang)
+            .with_context(|| format!("SubjectFiles::document not available in {:?}", lang))?;
+
+        let mut documents = self
+            .documents
+            .lock()
+            .map_err(|_| ah::anyhow!("SubjectFiles::document cache lock poisoned"))?;
+
+        if let Some(document) = documents.get(lang) {
+            return Ok(Arc::clone(document));
+        }
+
+        let document = Arc::new(Document::load(path).context("SubjectFiles::document parsing")?);
+        documents.insert(lang.clone(), Arc::clone(&document));
+
+        Ok(document)
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -68,7 +94,7 @@ impl XinY {
     /// internal subjects map. If a language is provided, it will be used in
     /// place of the directory name language tag (mainly for the root). This
     /// will not do so recurisvley, it only adds from the top level.
-    pub fn collect_subjects(&mut self, path: &PathBuf, language: Language) -> ah::Result<()> {
+    pub fn collect_subjects(&mut self, path: &Path, language: Language) -> ah::Result<()> {
         if !path.is_dir() {
             ah::bail!("XinY::collect_subjects path is not a directory");
         }
@@ -129,7 +155,7 @@ impl XinY {
     /// This will identify every language directory in the root directory and
     /// call `collect_subjects` on each of them, alongside the root directory
     /// itself, where the language is forced to English.
-    pub fn collect_from_root(&mut self, root_dir: &PathBuf) -> ah::Result<()> {
+    pub fn collect_from_root(&mut self, root_dir: &Path) -> ah::Result<()> {
         let read_dir = root_dir
             .read_dir()
             .context("XinY::collect_from_root reading root directory")?;
@@ -188,7 +214,7 @@ impl XinY {
         Ok(())
     }
 
-    pub fn new(root_dir: &PathBuf) -> ah::Result<Self> {
+    pub fn new(root_dir: &Path) -> ah::Result<Self> {
         let mut xiny = Self::default();
 
         xiny.collect_from_root(root_dir)
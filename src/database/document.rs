@@ -0,0 +1,445 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::search::engines::fuzzy::FuzzySearch;
+use crate::search::engines::SearchEngine;
+use crate::search::outline::{slug, Heading, Outline};
+use crate::search::scope::{block_range, tag_lines, BlockKind};
+use crate::search::symbols::fence_language;
+use crate::utils::read_lines;
+
+use anyhow::{self as ah, Context};
+
+/// The YAML front matter at the top of a document, e.g. its name and
+/// contributors. Only top level keys are told apart; the value of a key is
+/// its raw text, including any lines nested under it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrontMatter {
+    /// The lines of the front matter, including its `---` delimiters.
+    pub lines: Range<usize>,
+
+    /// Every top level key and its value, in document order.
+    pub fields: Vec<(String, String)>,
+}
+
+impl FrontMatter {
+    fn parse(lines: &[(usize, String, BlockKind)], range: Range<usize>) -> Self {
+        let mut fields: Vec<(String, String)> = Vec::new();
+
+        // Skips the delimiters, where the closing one may be missing.
+        let inner = lines[range.clone()]
+            .iter()
+            .skip(1)
+            .filter(|(_, line, _)| !matches!(line.trim_end(), "---" | "..."));
+
+        for (_, line, _) in inner {
+            let nested = line.starts_with(char::is_whitespace) || line.starts_with('-');
+
+            match line.split_once(':') {
+                Some((key, value)) if !nested && !key.contains(' ') => {
+                    let value = value.trim().trim_matches('"');
+                    fields.push((key.to_string(), value.to_string()));
+                }
+
+                _ => {
+                    if let Some((_, value)) = fields.last_mut() {
+                        if !value.is_empty() {
+                            value.push('\n');
+                        }
+
+                        value.push_str(line);
+                    }
+                }
+            }
+        }
+
+        FrontMatter {
+            lines: range,
+            fields,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The people listed under the key, e.g. contributors, whose entries are
+    /// written as - ["Name", "https://example.com"], with the URL optional.
+    pub fn people(&self, key: &str) -> Vec<Person> {
+        let Some(value) = self.get(key) else {
+            return Vec::new();
+        };
+
+        value
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix('-'))
+            .filter_map(|entry| {
+                let entry = entry.trim();
+
+                // Entries are mostly valid JSON, but not always, e.g. with
+                // single quotes, so they're split by hand as a fallback.
+                let parts: Vec<String> = serde_json::from_str(entry).unwrap_or_else(|_| {
+                    entry
+                        .trim_matches(['[', ']'])
+                        .split(',')
+                        .map(|part| part.trim().trim_matches(['"', '\'']).to_string())
+                        .collect()
+                });
+
+                let mut parts = parts.into_iter().filter(|part| !part.is_empty());
+
+                Some(Person {
+                    name: parts.next()?,
+                    url: parts.next(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A contributor or translator of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Person {
+    pub name: String,
+    pub url: Option<String>,
+}
+
+/// A fenced code block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The lines of the block, including its fences.
+    pub lines: Range<usize>,
+
+    /// The lines between the fences; up to the end of the document if the
+    /// block is never closed.
+    pub body: Range<usize>,
+
+    /// The language of the fence's info string, lowercased, e.g. python.
+    /// Empty if the fence doesn't name one.
+    pub language: String,
+}
+
+/// A block of a section that isn't a heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    /// A run of prose lines; paragraphs, lists, quotes, tables, etc.
+    Paragraph(Range<usize>),
+
+    Code(CodeBlock),
+}
+
+impl Block {
+    pub fn lines(&self) -> Range<usize> {
+        match self {
+            Block::Paragraph(lines) => lines.clone(),
+            Block::Code(code) => code.lines.clone(),
+        }
+    }
+}
+
+/// A heading and everything up to the next heading of the same or a higher
+/// level, where the sections of lower level headings are its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub heading: Heading,
+
+    /// The lines of the section, from its heading up to the next heading of
+    /// the same or a higher level, children included.
+    pub lines: Range<usize>,
+
+    /// The blocks between the heading and the first child.
+    pub blocks: Vec<Block>,
+
+    pub children: Vec<Section>,
+}
+
+impl Section {
+    /// The section and every section nested in it, in document order.
+    pub fn flatten(&self) -> Vec<&Section> {
+        std::iter::once(self)
+            .chain(self.children.iter().flat_map(Section::flatten))
+            .collect()
+    }
+
+    /// The lines from the heading up to its first child, or the whole
+    /// section if it has none.
+    pub fn own_lines(&self) -> Range<usize> {
+        let end = self
+            .children
+            .first()
+            .map_or(self.lines.end, |child| child.lines.start);
+
+        self.lines.start..end
+    }
+}
+
+/// Ends the innermost open section at the line, moving it into the one
+/// enclosing it, or to the top level.
+fn close(open: &mut Vec<Section>, top: &mut Vec<Section>, end: usize) {
+    let Some(mut section) = open.pop() else {
+        return;
+    };
+
+    section.lines.end = end;
+
+    match open.last_mut() {
+        Some(parent) => parent.children.push(section),
+        None => top.push(section),
+    }
+}
+
+/// A subject file parsed into its Markdown structure, shared by everything
+/// that needs more than its lines. Line ranges are zero-based line numbers,
+/// which are also the indices into lines.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub path: PathBuf,
+
+    /// Every line of the document, tagged with the Markdown block it's in.
+    pub lines: Vec<(usize, String, BlockKind)>,
+
+    pub outline: Outline,
+
+    pub front_matter: Option<FrontMatter>,
+
+    /// The blocks before the first heading.
+    pub blocks: Vec<Block>,
+
+    /// The sections of the top level headings, i.e. those not nested under
+    /// another heading.
+    pub sections: Vec<Section>,
+}
+
+impl Document {
+    pub fn load(path: &Path) -> ah::Result<Self> {
+        let lines = read_lines(path).context("Document::load reading document")?;
+        Ok(Self::parse(path, &lines))
+    }
+
+    pub fn parse(path: &Path, lines: &[(usize, String)]) -> Self {
+        let lines = tag_lines(lines);
+        let outline = Outline::new(&lines);
+
+        let mut document = Document {
+            path: path.to_path_buf(),
+            front_matter: None,
+            blocks: Vec::new(),
+            sections: Vec::new(),
+            lines,
+            outline,
+        };
+
+        // The sections being read, each nested in the one before it.
+        let mut open: Vec<Section> = Vec::new();
+        let mut headings = document.outline.headings.iter().peekable();
+        let mut index = 0;
+
+        while index < document.lines.len() {
+            let kind = document.lines[index].2;
+            let mut range = index..index + 1;
+
+            let block = match kind {
+                BlockKind::FrontMatter => {
+                    range = block_range(&document.lines, index);
+                    document.front_matter =
+                        Some(FrontMatter::parse(&document.lines, range.clone()));
+                    None
+                }
+
+                BlockKind::Heading => {
+                    if let Some(heading) = headings.next_if(|h| h.line_num == index) {
+                        while open
+                            .last()
+                            .is_some_and(|s| s.heading.level >= heading.level)
+                        {
+                            close(&mut open, &mut document.sections, index);
+                        }
+
+                        open.push(Section {
+                            heading: heading.clone(),
+                            lines: index..index,
+                            blocks: Vec::new(),
+                            children: Vec::new(),
+                        });
+                    }
+
+                    None
+                }
+
+                BlockKind::Fence | BlockKind::Code => {
+                    range = block_range(&document.lines, index);
+
+                    let closed =
+                        range.len() > 1 && document.lines[range.end - 1].2 == BlockKind::Fence;
+
+                    Some(Block::Code(CodeBlock {
+                        lines: range.clone(),
+                        body: range.start + 1..range.end - closed as usize,
+                        language: fence_language(&document.lines[index].1),
+                    }))
+                }
+
+                BlockKind::Prose => {
+                    range = block_range(&document.lines, index);
+                    Some(Block::Paragraph(range.clone()))
+                }
+
+                // The underline of a setext heading has no heading of its own.
+                BlockKind::Underline | BlockKind::Blank => None,
+            };
+
+            if let Some(block) = block {
+                match open.last_mut() {
+                    Some(section) => section.blocks.push(block),
+                    None => document.blocks.push(block),
+                }
+            }
+
+            index = range.end.max(index + 1);
+        }
+
+        while !open.is_empty() {
+            close(&mut open, &mut document.sections, document.lines.len());
+        }
+
+        document.outline.title = document
+            .front_matter
+            .as_ref()
+            .and_then(|fm| fm.get("name").or_else(|| fm.get("language")))
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
+
+        document
+    }
+
+    /// Every section, in document order, nested ones included.
+    pub fn all_sections(&self) -> Vec<&Section> {
+        self.sections.iter().flat_map(Section::flatten).collect()
+    }
+
+    /// The innermost section the line is in. None before the first heading.
+    pub fn section_at(&self, line_num: usize) -> Option<&Section> {
+        self.all_sections()
+            .into_iter()
+            .rev()
+            .find(|s| s.lines.contains(&line_num))
+    }
+
+    /// The section whose heading best matches the name, which may also be
+    /// an anchor, e.g. goroutines. Headings are tried by their exact text,
+    /// then by anchor, then as containing the name, and lastly fuzzily;
+    /// within each, the first one in the document wins.
+    pub fn find_section(&self, name: &str) -> Option<&Section> {
+        let sections = self.all_sections();
+        let name = name.trim().trim_start_matches('#');
+        let lowercase = name.to_lowercase();
+
+        let exact = |s: &&&Section| s.heading.text.to_lowercase() == lowercase;
+        let anchor = |s: &&&Section| slug(&s.heading.text) == slug(name);
+        let contains = |s: &&&Section| s.heading.text.to_lowercase().contains(&lowercase);
+
+        if let Some(found) = sections
+            .iter()
+            .find(exact)
+            .or_else(|| sections.iter().find(anchor))
+            .or_else(|| sections.iter().find(contains))
+        {
+            return Some(found);
+        }
+
+        let fuzzy = FuzzySearch::default();
+
+        // max_by_key picks the last of equal scores, hence the reversal.
+        sections
+            .iter()
+            .filter_map(|s| Some((fuzzy.match_term(&s.heading.text, name)?.0, s)))
+            .rev()
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, s)| *s)
+    }
+
+    /// Every code block, in document order.
+    pub fn code_blocks(&self) -> Vec<&CodeBlock> {
+        let sections = self.all_sections();
+
+        self.blocks
+            .iter()
+            .chain(sections.iter().flat_map(|s| &s.blocks))
+            .filter_map(|block| match block {
+                Block::Code(code) => Some(code),
+                Block::Paragraph(_) => None,
+            })
+            .collect()
+    }
+
+    /// The lines without their block kinds, as read from the file.
+    pub fn plain_lines(&self) -> Vec<(usize, String)> {
+        self.lines
+            .iter()
+            .map(|(line_num, line, _)| (*line_num, line.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let source = "---\nname: Go\ncontributors:\n    - [\"A\", \"a.com\"]\n---\nIntro\n\n\
+            # Basics\ntext\n```go\nfmt.Println()\n```\n## Defer\nmore\nText\n----\n\
+            ```\nunclosed";
+
+        let lines: Vec<(usize, String)> = source.lines().map(String::from).enumerate().collect();
+        let document = Document::parse(Path::new("go.md"), &lines);
+
+        let front_matter = document.front_matter.as_ref().unwrap();
+        assert_eq!(front_matter.lines, 0..5);
+        assert_eq!(front_matter.get("name"), Some("Go"));
+        assert_eq!(
+            front_matter.get("contributors"),
+            Some("    - [\"A\", \"a.com\"]")
+        );
+        assert_eq!(
+            front_matter.people("contributors"),
+            vec![Person {
+                name: "A".to_string(),
+                url: Some("a.com".to_string())
+            }]
+        );
+
+        assert_eq!(document.blocks, vec![Block::Paragraph(5..6)]);
+
+        let sections: Vec<(&str, Range<usize>)> = document
+            .all_sections()
+            .iter()
+            .map(|s| (s.heading.text.as_str(), s.lines.clone()))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![("Basics", 7..18), ("Defer", 12..14), ("Text", 14..18)]
+        );
+
+        let code: Vec<(Range<usize>, Range<usize>, &str)> = document
+            .code_blocks()
+            .iter()
+            .map(|c| (c.lines.clone(), c.body.clone(), c.language.as_str()))
+            .collect();
+        assert_eq!(code, vec![(9..12, 10..11, "go"), (16..18, 17..18, "")]);
+
+        assert_eq!(document.section_at(13).unwrap().heading.text, "Defer");
+        assert_eq!(document.section_at(10).unwrap().heading.text, "Basics");
+        assert!(document.section_at(5).is_none());
+
+        let found = |name: &str| document.find_section(name).map(|s| s.heading.line_num);
+        assert_eq!(found("defer"), Some(12));
+        assert_eq!(found("#basics"), Some(7));
+        assert_eq!(found("ext"), Some(14));
+        assert_eq!(found("dfr"), Some(12));
+        assert_eq!(found("goroutines"), None);
+    }
+}
@@ -2,4 +2,8 @@
 pub mod repository;
 
 // Exposes functions to retreive/interact with the xiny database.
+#[allow(clippy::module_inception)]
 pub mod database;
+
+// Subject files parsed into sections, code blocks and front matter.
+pub mod document;
@@ -1,5 +1,7 @@
 use std::{fs, path::PathBuf};
 
+use crate::database::database::XinY;
+use crate::search::index::Index;
 use crate::utils::shell;
 use anyhow::{self as ah, Context};
 use dirs;
@@ -31,17 +33,80 @@ impl Repo {
     }
 
     pub fn sync(&self, force: bool) -> ah::Result<bool> {
-        if !self.git_dir.exists() {
+        let changed = if !self.git_dir.exists() {
             self.clone(true)?;
-            return Ok(true);
+            true
+        } else if force || self.is_remote_ahead()? {
+            self.pull()?;
+            true
+        } else {
+            false
+        };
+
+        // Even if nothing changed, the index may be missing or outdated, e.g.
+        // if a previous build was interrupted.
+        if changed || matches!(self.load_index(), Ok(None)) {
+            self.build_index()
+                .context("Repo::sync rebuilding search index")?;
         }
 
-        if force || self.is_remote_ahead()? {
-            self.pull()?;
-            return Ok(true);
+        Ok(changed)
+    }
+
+    /// The commit hash the local repository currently has checked out.
+    pub fn head_commit(&self) -> ah::Result<String> {
+        let (stdout, stderr) = shell(
+            "git",
+            vec![
+                "--git-dir",
+                &self.git_dir.display().to_string(),
+                "rev-parse",
+                "HEAD",
+            ],
+        )?;
+
+        let commit_hash: String = stdout
+            .trim()
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .collect();
+
+        if commit_hash.is_empty() {
+            ah::bail!("Repo::head_commit rev-parse failed: {}", stderr.trim());
         }
 
-        Ok(false)
+        Ok(commit_hash)
+    }
+
+    /// The search index lives inside the git directory, so that it never
+    /// shows up in the working tree of the repository.
+    pub fn index_path(&self) -> PathBuf {
+        self.git_dir.join("xiny-index")
+    }
+
+    /// Loads the search index, but only if it was built from the commit that
+    /// is currently checked out; a stale or missing index yields None, which
+    /// rebuilding fixes. Fails if the commit can't be told in the first place,
+    /// e.g. without git, in which case no index can be built either.
+    pub fn load_index(&self) -> ah::Result<Option<Index>> {
+        let commit = self.head_commit()?;
+
+        Ok(Index::load(&self.index_path())
+            .ok()
+            .filter(|index| index.commit == commit))
+    }
+
+    /// (Re)builds the search index from the documents at the current commit.
+    pub fn build_index(&self) -> ah::Result<Index> {
+        let commit = self.head_commit()?;
+        let xiny = XinY::new(&self.repo_dir).context("Repo::build_index collecting subjects")?;
+        let index = Index::build(&xiny, &commit)?;
+
+        index
+            .save(&self.index_path())
+            .context("Repo::build_index saving index")?;
+
+        Ok(index)
     }
 
     // Checks if the latest commit hash of the local repository does not match
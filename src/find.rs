@@ -1,98 +1,378 @@
-use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
-use std::path::PathBuf;
-use std::sync::atomic::{self, AtomicBool, AtomicI64};
-use std::sync::Arc;
-
-use crossterm::{self as ct, queue};
-use crossterm::cursor::{Hide, Show};
-use crossterm::event::{self, Event, KeyCode};
-use crossterm::execute;
-use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
-use crossterm::style::{ContentStyle, PrintStyledContent, StyledContent};
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, is_raw_mode_enabled, Clear, ClearType, DisableLineWrap,
-    EnableLineWrap, EnterAlternateScreen, ScrollDown, ScrollUp, SetSize, WindowSize,
-};
-use std::collections::HashSet;
-
-use crate::xiny::*;
-use crate::futile::*;
+use std::io::{self, IsTerminal, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crossterm::queue;
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
 
 use anyhow::{self as ah, Context};
 
-pub enum FindMethod {
-    Fuzzy(String),
-    Regex(Vec<String>),
-    Terms(Vec<String>),
-}
+use crate::search::engines::{Match, SearchEngine, Span};
+use crate::search::query::{Origin, Query};
+use crate::search::scope::{block_range, BlockKind};
+
+/// The most lines smart context shows of a block, e.g. of a long code block.
+pub const SMART_CONTEXT_CAP: usize = 30;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
-pub enum LineLengthLimit {
-    // Positive values will limit the line length to that number of characters,
-    // negative values will subtract from the terminal's width, and a value of
-    // 0 will use the whole available width without risking line wrap.
-    TermOffset(isize),
+/// Which lines around a match are shown along with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchContext {
+    /// The Markdown block the match is in, e.g. the whole code block or the
+    /// paragraph, but no more than cap lines of it.
+    Smart { cap: usize },
 
-    /// Line cannot exceed N% of the terminal's width.
-    TermPercent(usize),
+    /// A fixed number of lines before and after the match.
+    Lines { before: usize, after: usize },
+
+    /// The entire document.
+    Document,
 }
 
-impl LineLengthLimit {
-    pub fn calculate_limit(&self) -> ah::Result<usize> {
-        match self {
-            LineLengthLimit::TermOffset(offset) => {
-                let (width, _): (u16, u16) = ct::terminal::size()?;
-                let width: isize = width as isize;
-                let offset: isize = *offset;
+impl Default for MatchContext {
+    fn default() -> Self {
+        MatchContext::Smart {
+            cap: SMART_CONTEXT_CAP,
+        }
+    }
+}
 
-                if offset < 0 {
-                    return Ok((width - offset) as usize);
-                }
+impl MatchContext {
+    /// The indices of the lines to show for the match, which are within the
+    /// document, and always include the lines of the match itself.
+    pub fn range(&self, lines: &[(usize, String, BlockKind)], matched: &Match) -> Range<usize> {
+        let matched_lines = matched.line_num..matched.end_line + 1;
+
+        match *self {
+            MatchContext::Document => 0..lines.len(),
 
-                Ok(offset as usize)
+            MatchContext::Lines { before, after } => {
+                matched_lines.start.saturating_sub(before)
+                    ..(matched_lines.end + after).min(lines.len())
             }
 
-            LineLengthLimit::TermPercent(percentage) => {
-                if *percentage > 100 {
-                    ah::bail!("Percentage cannot exceed 100");
-                }
+            MatchContext::Smart { cap } => {
+                let start = block_range(lines, matched_lines.start).start;
+                let end = block_range(lines, matched_lines.end - 1).end;
 
-                let (width, _): (u16, u16) = ct::terminal::size()?;
+                let cap = cap.max(matched_lines.len());
 
-                let width = width as f64;
-                let percentage = *percentage as f64;
+                if end - start <= cap {
+                    return start..end;
+                }
 
-                // It seems redundant, but it's better to be explicit about
-                // important constraints that would cause problems if violated.
-                // Another developer might not realize that implicit behavior
-                // is being relied upon for the program to function correctly.
-                let limit: f64 = (width * (percentage.floor() / 100.0)).round();
+                // Too long to show in full; show the part of the block around
+                // the match, centered on it as far as the block allows. An odd
+                // line out goes before the match, where comments explaining it
+                // usually are.
+                let spare = cap - matched_lines.len();
+                let start = matched_lines
+                    .start
+                    .saturating_sub(spare.div_ceil(2))
+                    .max(start);
+                let start = start.min(end - cap);
 
-                // Implicit behavior of casting to usize is not as universal
-                // as floor or round; floor always means floor, round always
-                // means round. You do no have to guess if the developer is
-                // intentionally making use of implicit behavior or not.
-                Ok(limit as usize)
+                start..start + cap
             }
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct FindOptions {
+    /// Which lines around every match to print alongside it.
+    pub context: MatchContext,
 
-#[derive(Debug, Default, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
-pub struct MatchedLine {
-    pub file: PathBuf,
-    pub line_num: usize,
-    pub content: String,
-    pub length: usize,
+    /// The maximum number of matches to print, or 0 to print all of them.
+    pub matches: usize,
+
+    /// The color to highlight the matched line with, if any. Ignored when
+    /// stdout is not a terminal, so that piped output stays plain.
+    pub highlight_color: Option<Color>,
 }
 
-impl MatchedLine {
-    pub fn new(file: PathBuf) -> Self {
+impl Default for FindOptions {
+    fn default() -> Self {
         Self {
-            file,
-            ..Default::default()
+            context: MatchContext::default(),
+            matches: 1,
+            highlight_color: Some(Color::Green),
         }
     }
 }
+
+/// Runs the query through the search engine, whose results are already
+/// ordered by relevance. If near is set, matches are windows of up to that many
+/// lines rather than single lines.
+pub fn find_with<SE: SearchEngine>(
+    engine: &mut SE,
+    lines: &[(usize, String, BlockKind)],
+    query: &Query,
+    origin: &Origin,
+    near: Option<usize>,
+) -> ah::Result<Vec<Match>> {
+    match near {
+        Some(near) => engine.search_near(lines, query, origin, near),
+        None => engine.search(lines, query, origin),
+    }
+}
+
+/// Merges overlapping spans into a sorted list of disjoint byte ranges.
+fn merge_spans(spans: &[Span]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = spans.iter().map(|s| s.bytes.clone()).collect();
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Prints the matched line in the highlight color, with the matched spans
+/// themselves inverted so they stand out from the rest of the line.
+pub fn print_highlighted(
+    stdout: &mut impl Write,
+    prefix: &str,
+    line: &str,
+    spans: &[Span],
+    color: Color,
+) -> ah::Result<()> {
+    queue!(stdout, SetForegroundColor(color), Print(prefix))?;
+
+    let mut cursor = 0;
+
+    for range in merge_spans(spans) {
+        queue!(
+            stdout,
+            Print(&line[cursor..range.start]),
+            SetAttribute(Attribute::Reverse),
+            Print(&line[range.clone()]),
+            SetAttribute(Attribute::NoReverse),
+        )?;
+
+        cursor = range.end;
+    }
+
+    queue!(stdout, Print(&line[cursor..]), ResetColor, Print('\n'))?;
+    Ok(())
+}
+
+/// Prints each match surrounded by its context lines, prefixed with their
+/// line numbers, and with the matched lines themselves highlighted. Matches
+/// with a breadcrumb attached are headed by it.
+pub fn print_matches(
+    lines: &[(usize, String, BlockKind)],
+    matches: &[Match],
+    options: &FindOptions,
+) -> ah::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    let color = options
+        .highlight_color
+        .filter(|_| io::stdout().is_terminal());
+
+    let shown = match options.matches {
+        0 => matches.len(),
+        n => n.min(matches.len()),
+    };
+
+    // Line numbers are displayed starting from 1, as editors do.
+    let padding = lines.len().to_string().len();
+    let separator = "-".repeat(80);
+
+    for (index, matched) in matches.iter().take(shown).enumerate() {
+        let shown = options.context.range(lines, matched);
+
+        let location = match matched.end_line > matched.line_num {
+            true => format!("lines {}-{}", matched.line_num + 1, matched.end_line + 1),
+            false => format!("line {}", matched.line_num + 1),
+        };
+
+        writeln!(
+            stdout,
+            "Match {}/{} at {} (score {}): {}",
+            index + 1,
+            matches.len(),
+            location,
+            matched.score,
+            matched.terms().join(", ")
+        )?;
+
+        if !matched.breadcrumb.is_empty() {
+            writeln!(stdout, "{}", matched.breadcrumb.join(" > "))?;
+        }
+
+        writeln!(stdout, "{}", separator)?;
+
+        for (line_num, line, _) in &lines[shown] {
+            let is_match = matched.contains_line(*line_num);
+            let marker = if is_match { '>' } else { ' ' };
+            let prefix = format!("{} {:>padding$}: ", marker, line_num + 1);
+
+            match color {
+                Some(color) if is_match => {
+                    let spans = matched.spans_on(*line_num);
+                    print_highlighted(&mut stdout, &prefix, line, &spans, color)?
+                }
+                _ => writeln!(stdout, "{}{}", prefix, line)?,
+            }
+        }
+
+        writeln!(stdout)?;
+    }
+
+    stdout.flush().context("print_matches flushing stdout")?;
+    Ok(())
+}
+
+/// Prints every match as `path:line:col:text`, the format of `grep -n
+/// --column` and ripgrep's --vimgrep, which Vim's :cexpr and Emacs' grep-mode
+/// can jump to. Every matched span gets its own entry, in document order, with
+/// the line and (byte) column numbers starting from 1.
+pub fn print_vimgrep(path: &Path, lines: &[(usize, String)], matches: &[Match]) -> ah::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    let mut matches: Vec<&Match> = matches.iter().collect();
+    matches.sort_by_key(|m| m.line_num);
+
+    for matched in matches {
+        let mut locations: Vec<(usize, usize)> = matched
+            .spans
+            .iter()
+            .map(|s| (s.line_num, s.bytes.start))
+            .collect();
+
+        locations.dedup();
+
+        if locations.is_empty() {
+            locations.push((matched.line_num, 0));
+        }
+
+        for (line_num, col) in locations {
+            let Some((line_num, line)) = lines.get(line_num) else {
+                continue;
+            };
+
+            writeln!(
+                stdout,
+                "{}:{}:{}:{}",
+                path.display(),
+                line_num + 1,
+                col + 1,
+                line
+            )?;
+        }
+    }
+
+    stdout.flush().context("print_vimgrep flushing stdout")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_with() {
+        use crate::search::engines::terms::TermSearch;
+        use crate::search::scope::tag_lines;
+
+        let lines: Vec<(usize, String)> = ["abc", "List comprehension", "a list", "nothing"]
+            .iter()
+            .map(|s| s.to_string())
+            .enumerate()
+            .collect();
+
+        let lines = tag_lines(&lines);
+
+        let find = |query: &str| -> Vec<Match> {
+            let query = Query::parse(query).unwrap();
+            let origin = Origin::default();
+            find_with(&mut TermSearch::default(), &lines, &query, &origin, None).unwrap()
+        };
+
+        let matches = find("list comprehension");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].terms(), vec!["list", "comprehension"]);
+
+        let found: Vec<usize> = find("list | comprehension")
+            .iter()
+            .map(|m| m.line_num)
+            .collect();
+        assert_eq!(found, vec![1, 2]);
+
+        let matches = find("list -comprehension");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].spans[0].bytes, 2..6);
+    }
+
+    #[test]
+    fn test_context_range() {
+        use crate::search::scope::tag_lines;
+
+        let document =
+            "# Lists\nA list\nholds values.\n\n```python\nli = []\nli.append(1)\n```\nend";
+
+        let lines: Vec<(usize, String)> = document.lines().map(String::from).enumerate().collect();
+        let lines = tag_lines(&lines);
+
+        let range = |context: MatchContext, line_num: usize| {
+            let matched = Match {
+                line_num,
+                end_line: line_num,
+                ..Default::default()
+            };
+
+            context.range(&lines, &matched)
+        };
+
+        let smart = MatchContext::default();
+        assert_eq!(range(smart, 6), 4..8);
+        assert_eq!(range(smart, 2), 1..3);
+        assert_eq!(range(smart, 0), 0..3);
+        assert_eq!(range(MatchContext::Smart { cap: 2 }, 6), 5..7);
+
+        let lines_around = MatchContext::Lines {
+            before: 1,
+            after: 3,
+        };
+        assert_eq!(range(lines_around, 0), 0..4);
+        assert_eq!(range(lines_around, 7), 6..9);
+        assert_eq!(range(MatchContext::Document, 3), 0..9);
+    }
+
+    #[test]
+    fn test_find_near() {
+        use crate::search::engines::terms::TermSearch;
+        use crate::search::scope::tag_lines;
+
+        let document = "# Reverse the list\nli.reverse()\nunrelated\nreverse\nthe list\nlist";
+
+        let lines: Vec<(usize, String)> = document.lines().map(String::from).enumerate().collect();
+        let lines = tag_lines(&lines);
+
+        let find = |query: &str, near: usize| -> Vec<(usize, usize)> {
+            let query = Query::parse(query).unwrap();
+            let origin = Origin::default();
+
+            find_with(&mut TermSearch::default(), &lines, &query, &origin, Some(near))
+                .unwrap()
+                .iter()
+                .map(|m| (m.line_num, m.end_line))
+                .collect()
+        };
+
+        // The tightest window ranks first, and windows overlapping it are
+        // dropped, even though they're just as tight.
+        assert_eq!(find("li.reverse comment", 2), vec![]);
+        assert_eq!(find("reverse() list", 2), vec![(0, 1)]);
+        assert_eq!(find("reverse list", 3), vec![(0, 0), (3, 4)]);
+        assert_eq!(find("reverse -the", 3), vec![(1, 1), (3, 3)]);
+        assert_eq!(find("code:reverse list", 1), vec![]);
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use anyhow as ah;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::database::database::SubjectFiles;
+use crate::database::document::{Document, Person};
+use crate::language::language::Language;
+
+/// How output meant for both people and scripts is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    /// Aligned, human readable text.
+    #[default]
+    Text,
+
+    /// A single JSON object.
+    Json,
+}
+
+/// What the front matter of a subject's document says about it, along with
+/// where it is and which other languages it's available in.
+#[derive(Debug, Clone)]
+pub struct SubjectInfo {
+    pub subject: String,
+
+    /// The name the document goes by, e.g. Go for go; the subject if the
+    /// front matter doesn't say.
+    pub name: String,
+
+    pub category: Option<String>,
+
+    /// The name of the file the document's code is meant to be saved as,
+    /// e.g. learngo.go.
+    pub filename: Option<String>,
+
+    pub contributors: Vec<Person>,
+    pub translators: Vec<Person>,
+
+    /// The language of the document.
+    pub lang: String,
+
+    pub path: PathBuf,
+    pub line_count: usize,
+
+    /// The tags of every language the subject is available in.
+    pub translations: Vec<String>,
+}
+
+impl SubjectInfo {
+    pub fn new(subject: &str, files: &SubjectFiles, document: &Document, lang: &Language) -> Self {
+        let front_matter = document.front_matter.clone().unwrap_or_default();
+        let field = |key: &str| front_matter.get(key).map(str::to_string);
+
+        SubjectInfo {
+            subject: subject.to_string(),
+            name: document
+                .outline
+                .title
+                .clone()
+                .unwrap_or_else(|| subject.to_string()),
+            category: field("category"),
+            filename: field("filename"),
+            contributors: front_matter.people("contributors"),
+            translators: front_matter.people("translators"),
+            lang: lang.to_tag(),
+            path: document.path.clone(),
+            line_count: document.lines.len(),
+            translations: files
+                .available_languages()
+                .iter()
+                .map(|l| l.to_tag())
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let people = |people: &[Person]| -> Vec<Value> {
+            people
+                .iter()
+                .map(|p| json!({ "name": p.name, "url": p.url }))
+                .collect()
+        };
+
+        json!({
+            "subject": self.subject,
+            "name": self.name,
+            "category": self.category,
+            "filename": self.filename,
+            "contributors": people(&self.contributors),
+            "translators": people(&self.translators),
+            "lang": self.lang,
+            "path": self.path.display().to_string(),
+            "lines": self.line_count,
+            "translations": self.translations,
+        })
+    }
+
+    pub fn print(&self, format: Format) -> ah::Result<()> {
+        let mut stdout = io::stdout().lock();
+
+        if format == Format::Json {
+            writeln!(stdout, "{}", serde_json::to_string_pretty(&self.to_json())?)?;
+            return Ok(());
+        }
+
+        let person = |p: &Person| match &p.url {
+            Some(url) => format!("{} <{}>", p.name, url),
+            None => p.name.clone(),
+        };
+
+        let rows: Vec<(&str, Vec<String>)> = vec![
+            ("Name", vec![self.name.clone()]),
+            ("Category", self.category.iter().cloned().collect()),
+            ("Filename", self.filename.iter().cloned().collect()),
+            (
+                "Contributors",
+                self.contributors.iter().map(person).collect(),
+            ),
+            ("Translators", self.translators.iter().map(person).collect()),
+            ("Language", vec![self.lang.clone()]),
+            ("Path", vec![self.path.display().to_string()]),
+            ("Lines", vec![self.line_count.to_string()]),
+            ("Translations", vec![self.translations.join(", ")]),
+        ];
+
+        // Rows the front matter had nothing for are left out.
+        for (label, values) in rows.iter().filter(|(_, values)| !values.is_empty()) {
+            for (i, value) in values.iter().enumerate() {
+                let label = if i == 0 { *label } else { "" };
+                writeln!(stdout, "{:<14}{}", label, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
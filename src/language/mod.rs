@@ -1,2 +1,3 @@
 mod iana_tags;
+#[allow(clippy::module_inception)]
 pub mod language;
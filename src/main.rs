@@ -9,19 +9,42 @@ use config::parser::*;
 pub mod argparse;
 pub mod config;
 pub mod database;
+pub mod find;
+pub mod info;
 pub mod language;
 pub mod render;
 pub mod search;
+pub mod sections;
 pub mod tui;
 pub mod utils;
 
 use database::database::XinY;
+use database::document::{Document, Section};
 use database::repository::Repo;
+use find::MatchContext;
+use info::SubjectInfo;
 use language::language::Language;
-use search::engines::terms::TermSearch;
+use search::answer;
+use search::engines::any::AnyEngine;
+use search::engines::external;
+use search::engines::EngineKind;
+use search::global::{print_hits, print_hits_vimgrep, search_all, search_symbol, SearchOptions};
+use search::normalize::Normalizer;
+use search::query::{Origin, Query};
 use tui::event_loop::{self};
 
-fn handle_set_conf(set_conf: &Vec<String>, conf: &mut ConfigFile) -> ah::Result<()> {
+/// The section of the document under the heading, exiting if there's none.
+fn find_section<'a>(document: &'a Document, heading: &str, subject_name: &str) -> &'a Section {
+    document.find_section(heading).unwrap_or_else(|| {
+        eprintln!(
+            "Section not found: {}. Try `xiny {} --toc` to see its headings.",
+            heading, subject_name
+        );
+        exit(1);
+    })
+}
+
+fn handle_set_conf(set_conf: &[String], conf: &mut ConfigFile) -> ah::Result<()> {
     if set_conf.len() != 2 {
         ah::bail!(
             "Expected 2 arguments (KEY VALUE), got {}",
@@ -47,7 +70,7 @@ fn handle_set_conf(set_conf: &Vec<String>, conf: &mut ConfigFile) -> ah::Result<
     Ok(())
 }
 
-fn handle_get_conf(get_conf: &Vec<String>, conf: &mut ConfigFile) -> ah::Result<()> {
+fn handle_get_conf(get_conf: &[String], conf: &mut ConfigFile) -> ah::Result<()> {
     if get_conf.is_empty() {
         println!("{}", conf.values.dump());
     } else if get_conf.len() == 1 {
@@ -136,6 +159,47 @@ fn main() -> ah::Result<()> {
 
     let xiny = XinY::new(&repo.repo_dir).context("XinY::new")?;
 
+    if cli.near == Some(0) {
+        eprintln!("--near must be at least 1 line.");
+        exit(1);
+    }
+
+    let normalizer = Normalizer {
+        case: cli.case,
+        fold_diacritics: cli.fold_diacritics,
+        stem: cli.stem,
+    };
+
+    // A flag picks the engine, or else the engine config key does.
+    let engine = if cli.regex {
+        EngineKind::Regex
+    } else if cli.fuzzy {
+        EngineKind::Fuzzy
+    } else if cli.external {
+        EngineKind::External
+    } else {
+        EngineKind::from_name(&config.values.engine).unwrap_or_else(|| {
+            eprintln!(
+                "Invalid engine in config: {}. Expected terms, regex, fuzzy or external.",
+                config.values.engine
+            );
+            exit(1);
+        })
+    };
+
+    let engine = match engine {
+        EngineKind::External if !external::available(&config.values.search_command) => {
+            eprintln!(
+                "Search command not found: {}. Falling back to the built-in term search.",
+                config.values.search_command
+            );
+            EngineKind::Terms
+        }
+        engine => engine,
+    };
+
+    let mut search_engine = AnyEngine::of(engine, normalizer, &config.values.search_command);
+
     if cli.list {
         let mut subjects = xiny.available_subjects();
 
@@ -196,13 +260,146 @@ fn main() -> ah::Result<()> {
         exit(0);
     }
 
+    if let Some(terms) = &cli.search {
+        let lang = Language::from_tag(cli.lang.as_deref().unwrap_or("en-us")).unwrap_or_else(|e| {
+            eprintln!("Invalid language tag: {:?}, err: {:?}", cli.lang, e);
+            exit(1);
+        });
+
+        let query = Query::parse(&terms.join(" ")).unwrap_or_else(|e| {
+            eprintln!("Invalid query: {:#}", e);
+            exit(1);
+        });
+
+        let index = repo.load_index();
+
+        let options = SearchOptions {
+            scope: cli.scope,
+            normalizer,
+            near: cli.near,
+            collapse: cli.collapse,
+        };
+
+        let results = search_all(
+            &search_engine,
+            &xiny,
+            &lang,
+            &query,
+            options,
+            index.as_ref().ok().and_then(Option::as_ref),
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("{:#}", e);
+            exit(1);
+        });
+
+        if results.is_empty() {
+            eprintln!("No matches found for: {}", terms.join(" "));
+        } else if cli.vimgrep {
+            print_hits_vimgrep(&results)?;
+        } else {
+            print_hits(&results)?;
+        }
+
+        // The documents were scanned in full this time around; rebuild the
+        // index so that the next search can make use of it. Without a commit
+        // to build it for, searches simply go without one.
+        if matches!(index, Ok(None)) {
+            if let Err(e) = repo.build_index() {
+                eprintln!("Failed to rebuild the search index: {:#}", e);
+            }
+        }
+
+        exit(if results.is_empty() { 1 } else { 0 });
+    }
+
+    if let Some(symbol) = &cli.symbol {
+        let lang = Language::from_tag(cli.lang.as_deref().unwrap_or("en-us")).unwrap_or_else(|e| {
+            eprintln!("Invalid language tag: {:?}, err: {:?}", cli.lang, e);
+            exit(1);
+        });
+
+        let subject = cli
+            .explicit_subject
+            .as_ref()
+            .or(cli.implicit_subject.as_ref());
+
+        if let Some(subject) = subject {
+            if xiny.get_subject(subject).is_none() {
+                eprintln!(
+                    "Subject not found: {}. Try `xiny --list` to see available subjects.",
+                    subject
+                );
+                exit(1);
+            }
+        }
+
+        let index = repo.load_index();
+
+        let results = search_symbol(
+            &xiny,
+            &lang,
+            subject.map(String::as_str),
+            symbol,
+            cli.collapse,
+            index.as_ref().ok().and_then(Option::as_ref),
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("{:#}", e);
+            exit(1);
+        });
+
+        if results.is_empty() {
+            eprintln!("No code found using the symbol: {}", symbol);
+        } else if cli.vimgrep {
+            print_hits_vimgrep(&results)?;
+        } else {
+            print_hits(&results)?;
+        }
+
+        if matches!(index, Ok(None)) {
+            if let Err(e) = repo.build_index() {
+                eprintln!("Failed to rebuild the search index: {:#}", e);
+            }
+        }
+
+        exit(if results.is_empty() { 1 } else { 0 });
+    }
+
     let subject_name: Option<String> = cli.explicit_subject.or(cli.implicit_subject);
 
-    if let Some(subject) = subject_name {
-        let subject = xiny.get_subject(&subject).unwrap_or_else(|| {
+    if let Some(subject_name) = subject_name {
+        // The anchor of a section may follow the subject, e.g. go#goroutines.
+        let (subject_name, anchor) = match subject_name.split_once('#') {
+            Some((subject, anchor)) if !subject.contains('/') => {
+                (subject.to_string(), Some(anchor.to_string()))
+            }
+            _ => (subject_name, None),
+        };
+
+        // A question may follow the subject, e.g. python/reverse a list.
+        let (subject_name, question) = match subject_name.split_once('/') {
+            Some((subject, question)) => {
+                let mut words = vec![question.to_string()];
+                words.extend(cli.implicit_question.iter().cloned());
+                (subject.to_string(), Some(words.join(" ")))
+            }
+            None if !cli.implicit_question.is_empty() => {
+                eprintln!(
+                    "Unexpected arguments: {}. To ask a question, use {}/{} or --ask.",
+                    cli.implicit_question.join(" "),
+                    subject_name,
+                    cli.implicit_question.join(" ")
+                );
+                exit(1);
+            }
+            None => (subject_name, cli.ask.as_ref().map(|words| words.join(" "))),
+        };
+
+        let subject = xiny.get_subject(&subject_name).unwrap_or_else(|| {
             eprintln!(
                 "Subject not found: {}. Try `xiny --list` to see available subjects.",
-                subject
+                subject_name
             );
             exit(1);
         });
@@ -220,21 +417,210 @@ fn main() -> ah::Result<()> {
             exit(1);
         });
 
+        let unscoped = cli.find.is_some() || cli.interactive || cli.r#where || cli.info || cli.toc;
+
+        if anchor.is_some() && (question.is_some() || unscoped) {
+            eprintln!(
+                "A section anchor can't be combined with a question, --find, --interactive, \
+                --where, --info or --toc."
+            );
+            exit(1);
+        }
+
         if cli.r#where {
             println!("{}", document_path.display());
             exit(0);
         }
 
-        if cli.find.is_some() {
-            eprintln!("--find is not yet implemented.");
-            exit(1);
+        if cli.info {
+            let document = subject
+                .document(&lang)
+                .context("reading document for --info")?;
+
+            SubjectInfo::new(&subject_name, subject, &document, &lang)
+                .print(cli.format.unwrap_or_default())?;
+            exit(0);
+        }
+
+        if cli.toc {
+            let document = subject
+                .document(&lang)
+                .context("reading document for --toc")?;
+
+            sections::print_toc(&document, cli.format.unwrap_or_default())?;
+            exit(0);
+        }
+
+        let heading = cli.section.as_ref().or(anchor.as_ref());
+
+        if cli.code || cli.code_out.is_some() {
+            let document = subject
+                .document(&lang)
+                .context("reading document for --code")?;
+
+            let section = heading.map(|heading| find_section(&document, heading, &subject_name));
+            let mut blocks = sections::code_blocks(&document, section);
+
+            if let Some(n) = cli.block {
+                let Some(block) = n.checked_sub(1).and_then(|i| blocks.get(i)) else {
+                    eprintln!("No code block {}; there are {}.", n, blocks.len());
+                    exit(1);
+                };
+
+                blocks = vec![*block];
+            }
+
+            if blocks.is_empty() {
+                eprintln!("No code blocks found.");
+                exit(1);
+            }
+
+            match &cli.code_out {
+                Some(dir) => {
+                    let path = sections::write_code(dir, &document, &subject_name, &blocks)?;
+                    println!("{}", path.display());
+                }
+                None => print!("{}", sections::code_text(&document, &blocks)),
+            }
+
+            exit(0);
+        }
+
+        if let Some(heading) = heading {
+            let document = subject
+                .document(&lang)
+                .context("reading document for --section")?;
+
+            let section = find_section(&document, heading, &subject_name);
+
+            let renderer = &config.values.renderer;
+            let renderer = (!renderer.is_empty()).then_some(renderer.as_str());
+            let markdown = sections::section_markdown(&document, section);
+
+            if let Err(e) = render::print_markdown(&markdown, &subject_name, renderer) {
+                eprintln!("Error rendering section: {:?}", e);
+                exit(1);
+            }
+
+            exit(0);
+        }
+
+        let origin = Origin {
+            subject: subject_name.clone(),
+            lang: lang.to_tag(),
+        };
+
+        if let Some(question) = question {
+            if cli.find.is_some() || cli.interactive || cli.near.is_some() {
+                eprintln!("A question can't be combined with --find, --interactive or --near.");
+                exit(1);
+            }
+
+            let terms = answer::question_terms(&question);
+
+            if terms.is_empty() {
+                eprintln!(
+                    "Expected a question after {}/, e.g. {}/reverse a list",
+                    subject_name, subject_name
+                );
+                exit(1);
+            }
+
+            let document = subject
+                .document(&lang)
+                .context("reading document for --ask")?;
+
+            let answers = answer::rank_sections(&mut search_engine, &document, &terms, &origin)
+                .unwrap_or_else(|e| {
+                    eprintln!("{:#}", e);
+                    exit(1);
+                });
+
+            if answers.is_empty() {
+                eprintln!("No section answers: {}", question);
+                exit(1);
+            }
+
+            answer::print_answers(&document.lines, &answers, cli.matches.unwrap_or(1))?;
+            exit(0);
+        }
+
+        // Like grep, --before-context and --after-context override either side
+        // of --context. Without any of them, the block of the match is shown.
+        let context = match (cli.context, cli.before_context, cli.after_context) {
+            (None, None, None) => MatchContext::default(),
+            (Some(0), None, None) => MatchContext::Document,
+            (lines, before, after) => MatchContext::Lines {
+                before: before.or(lines).unwrap_or(0),
+                after: after.or(lines).unwrap_or(0),
+            },
+        };
+
+        if let Some(terms) = &cli.find {
+            let query = Query::parse(&terms.join(" ")).unwrap_or_else(|e| {
+                eprintln!("Invalid query: {:#}", e);
+                exit(1);
+            });
+
+            let document = subject
+                .document(&lang)
+                .context("reading document for --find")?;
+            let tagged = &document.lines;
+            let searchable = cli.scope.select(tagged);
+
+            let mut matches = find::find_with(
+                &mut search_engine,
+                &searchable,
+                &query,
+                &origin,
+                cli.near,
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("{:#}", e);
+                exit(1);
+            });
+
+            if matches.is_empty() {
+                eprintln!("No matches found for: {}", terms.join(" "));
+                exit(1);
+            }
+
+            document.outline.annotate(&mut matches);
+
+            if cli.collapse {
+                matches = document.outline.collapse(matches);
+            }
+
+            if cli.vimgrep {
+                find::print_vimgrep(document_path, &document.plain_lines(), &matches)?;
+                exit(0);
+            }
+
+            let options = find::FindOptions {
+                context,
+                matches: cli.matches.unwrap_or(1),
+                ..Default::default()
+            };
+
+            find::print_matches(tagged, &matches, &options)?;
+            exit(0);
         }
 
         let renderer = (!config.values.renderer.is_empty()).then_some(config.values.renderer);
+        let document = subject.document(&lang).context("reading document")?;
 
         if cli.interactive {
-            event_loop::event_loop::<TermSearch>(document_path.to_path_buf())?;
-        } else if let Err(e) = render::print_document(document_path, renderer.as_deref()) {
+            let options = event_loop::EventLoopOptions {
+                scope: cli.scope,
+                normalizer,
+                near: cli.near,
+                context,
+                engine: search_engine,
+                search_command: config.values.search_command.clone(),
+            };
+
+            event_loop::event_loop(document, origin, options)?;
+        } else if let Err(e) = render::print_document(&document, renderer.as_deref()) {
             eprintln!("Error rendering document: {:?}", e);
             exit(1);
         }
@@ -1,8 +1,13 @@
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::Path;
 use std::process;
 
 use anyhow::{self as ah, Context};
 
+use crate::database::document::Document;
+
+pub mod builtin;
+
 const FALLBACK_VIEWERS: &[&str] = &["glow", "mdt", "bat", "less"];
 
 fn viewer_in_path(name: &str) -> bool {
@@ -12,29 +17,64 @@ fn viewer_in_path(name: &str) -> bool {
         .any(|dir| std::path::Path::new(dir).join(name).exists())
 }
 
-/// Outputs the Markdown document using the preferred renderer, falling back
-pub fn print_document(path: &PathBuf, preferred: Option<&str>) -> ah::Result<()> {
-    if !path.exists() {
-        return Err(ah::anyhow!("Document does not exist: {}", path.display()));
+/// The external viewer to render with: the preferred renderer if it's in the
+/// PATH, or else the first fallback viewer that is. None if there are none,
+/// or if the builtin renderer is preferred.
+fn external_renderer(preferred: Option<&str>) -> Option<&str> {
+    if preferred == Some(builtin::NAME) {
+        return None;
     }
 
-    let renderer = preferred
+    preferred
         .filter(|r| !r.is_empty() && viewer_in_path(r))
-        .or_else(|| FALLBACK_VIEWERS.iter().find(|&&r| viewer_in_path(r)).copied());
+        .or_else(|| FALLBACK_VIEWERS.iter().find(|&&r| viewer_in_path(r)).copied())
+}
 
-    match renderer {
+/// Outputs the Markdown document using the preferred renderer, falling back
+/// to the first viewer found in the PATH, and to the builtin renderer if there
+/// are none.
+pub fn print_document(document: &Document, preferred: Option<&str>) -> ah::Result<()> {
+    match external_renderer(preferred) {
         Some(r) => {
             process::Command::new(r)
-                .arg(path.display().to_string())
+                .arg(document.path.display().to_string())
                 .status()
                 .context("print_document spawning renderer")?;
         }
-        None => {
-            let document =
-                std::fs::read_to_string(path).context("print_document reading document")?;
-            print!("{}", document);
-        }
+        None => builtin::print(document)?,
     }
 
     Ok(())
 }
+
+/// Outputs Markdown that isn't a file of its own, e.g. a single section of a
+/// document, the same way print_document does. External viewers read it from
+/// their stdin, and the builtin renderer parses it as a document named name.
+pub fn print_markdown(markdown: &str, name: &str, preferred: Option<&str>) -> ah::Result<()> {
+    let Some(r) = external_renderer(preferred) else {
+        let lines: Vec<(usize, String)> = markdown.lines().map(String::from).enumerate().collect();
+        return builtin::print(&Document::parse(Path::new(name), &lines));
+    };
+
+    let mut child = process::Command::new(r)
+        .arg("-")
+        .stdin(process::Stdio::piped())
+        .spawn()
+        .context("print_markdown spawning renderer")?;
+
+    // The stdin is dropped once written, so that the viewer sees its end. A
+    // viewer quit before reading all of it, e.g. less, closes the pipe early.
+    let written = child
+        .stdin
+        .take()
+        .map_or(Ok(()), |mut stdin| stdin.write_all(markdown.as_bytes()));
+
+    child.wait().context("print_markdown waiting for renderer")?;
+
+    match written {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(e).context("print_markdown writing to renderer")
+        }
+        _ => Ok(()),
+    }
+}
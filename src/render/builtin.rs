@@ -0,0 +1,692 @@
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::queue;
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+
+use anyhow::{self as ah, Context};
+
+use crate::database::document::Document;
+use crate::search::outline::heading_text;
+use crate::search::scope::BlockKind;
+use crate::search::symbols::fence_language;
+use crate::utils::Dimensions;
+
+/// The renderer config value that selects this renderer.
+pub const NAME: &str = "builtin";
+
+/// How a piece of text is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    dim: bool,
+    color: Option<Color>,
+}
+
+/// A run of non-whitespace text, made up of differently styled pieces, e.g.
+/// `code`, followed by a comma.
+type Word = Vec<(String, Style)>;
+
+fn word_width(word: &Word) -> usize {
+    word.iter().map(|(text, _)| text.chars().count()).sum()
+}
+
+/// Moves the text collected so far into a piece of its own.
+fn flush(plain: &mut String, style: Style, pieces: &mut Vec<(String, Style)>) {
+    if !plain.is_empty() {
+        pieces.push((std::mem::take(plain), style));
+    }
+}
+
+/// Splits the inline Markdown of a line into styled pieces: `code`, **bold**,
+/// *emphasis* and _emphasis_, and [links](url), whose URL follows their text.
+/// Backslashes escape the character after them.
+fn inline(text: &str, base: Style) -> Vec<(String, Style)> {
+    let mut pieces: Vec<(String, Style)> = Vec::new();
+    let mut style = base;
+    let mut plain = String::new();
+
+    let chars: Vec<char> = text.chars().collect();
+    let rest = |i: usize| chars[i..].iter().collect::<String>();
+
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let before = i.checked_sub(1).map(|j| chars[j]);
+        let after = chars.get(i + 1).copied();
+
+        match c {
+            '\\' if after.is_some_and(|a| a.is_ascii_punctuation()) => {
+                plain.push(chars[i + 1]);
+                i += 2;
+            }
+
+            '`' => {
+                let ticks = chars[i..].iter().take_while(|&&x| x == '`').count();
+                let fence = "`".repeat(ticks);
+                let body = rest(i + ticks);
+
+                match body.find(&fence) {
+                    Some(end) => {
+                        flush(&mut plain, style, &mut pieces);
+                        let code = Style {
+                            color: Some(Color::Yellow),
+                            ..style
+                        };
+                        pieces.push((body[..end].trim().to_string(), code));
+                        i += ticks + body[..end].chars().count() + ticks;
+                    }
+                    None => {
+                        plain.push_str(&fence);
+                        i += ticks;
+                    }
+                }
+            }
+
+            '*' | '_' if after == Some(c) => {
+                let closing = style.bold != base.bold;
+
+                if closing || rest(i + 2).contains(&format!("{}{}", c, c)) {
+                    flush(&mut plain, style, &mut pieces);
+                    style.bold = !style.bold;
+                } else {
+                    plain.push_str(&format!("{}{}", c, c));
+                }
+
+                i += 2;
+            }
+
+            // An underscore within a word, e.g. in snake_case, isn't emphasis.
+            '_' if before.is_some_and(char::is_alphanumeric)
+                && after.is_some_and(char::is_alphanumeric) =>
+            {
+                plain.push(c);
+                i += 1;
+            }
+
+            '*' | '_' => {
+                let closing = style.italic != base.italic;
+                let opening = after.is_some_and(|a| !a.is_whitespace()) && rest(i + 1).contains(c);
+
+                if closing || opening {
+                    flush(&mut plain, style, &mut pieces);
+                    style.italic = !style.italic;
+                } else {
+                    plain.push(c);
+                }
+
+                i += 1;
+            }
+
+            '[' => {
+                let link = rest(i + 1);
+
+                let parts = link.split_once("](").and_then(|(label, target)| {
+                    let (url, _) = target.split_once(')')?;
+                    (!label.contains(']')).then_some((label.to_string(), url.to_string()))
+                });
+
+                let Some((label, url)) = parts else {
+                    plain.push(c);
+                    i += 1;
+                    continue;
+                };
+
+                flush(&mut plain, style, &mut pieces);
+
+                let underlined = Style {
+                    underlined: true,
+                    ..style
+                };
+                pieces.extend(inline(&label, underlined));
+
+                // Links to anchors of the document itself say nothing useful.
+                if !url.starts_with('#') {
+                    let dim = Style { dim: true, ..style };
+                    pieces.push((format!(" ({})", url), dim));
+                }
+
+                i += 1 + label.chars().count() + 2 + url.chars().count() + 1;
+            }
+
+            _ => {
+                plain.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    flush(&mut plain, style, &mut pieces);
+    pieces
+}
+
+/// Splits styled pieces into words at whitespace.
+fn words(pieces: &[(String, Style)]) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut word: Word = Vec::new();
+
+    for (text, style) in pieces {
+        let mut current = String::new();
+
+        for c in text.chars() {
+            if !c.is_whitespace() {
+                current.push(c);
+                continue;
+            }
+
+            if !current.is_empty() {
+                word.push((std::mem::take(&mut current), *style));
+            }
+
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+
+        if !current.is_empty() {
+            word.push((current, *style));
+        }
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    words
+}
+
+/// A block of prose being collected, line by line, before it's wrapped.
+#[derive(Debug, Clone)]
+struct Pending {
+    /// What the first line starts with, e.g. a bullet.
+    first_prefix: String,
+
+    /// What every other line starts with, so that they line up after it.
+    prefix: String,
+
+    text: String,
+    style: Style,
+    quote: bool,
+}
+
+/// Whether the line is a list item, and if so, its indentation, its marker,
+/// e.g. - or 1., and its text.
+fn list_item(line: &str) -> Option<(usize, &str, &str)> {
+    let indent = line.len() - line.trim_start().len();
+    let trimmed = line.trim_start();
+
+    let marker_len = match trimmed.chars().next()? {
+        '-' | '*' | '+' => 1,
+        c if c.is_ascii_digit() => {
+            let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+            let punctuation = trimmed[digits..].chars().next()?;
+            matches!(punctuation, '.' | ')').then_some(digits + 1)?
+        }
+        _ => return None,
+    };
+
+    let text = trimmed[marker_len..].strip_prefix([' ', '\t'])?;
+    Some((indent, &trimmed[..marker_len], text.trim_start()))
+}
+
+/// Whether the line is a thematic break, e.g. --- or ***.
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+
+    compact.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&c| compact.chars().all(|x| x == c))
+}
+
+/// The cells of a table row, without the pipes around them.
+fn table_cells(line: &str) -> Vec<String> {
+    line.trim()
+        .trim_start_matches('|')
+        .trim_end_matches('|')
+        .split('|')
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+/// Whether the row separates the header of a table from its body, e.g.
+/// |---|:--:|
+fn is_table_separator(cells: &[String]) -> bool {
+    cells.iter().all(|cell| {
+        let dashes = cell.trim_matches(':');
+        !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+    })
+}
+
+/// Renders Markdown for the terminal, wrapping prose to a width.
+struct Renderer<'a, W: Write> {
+    out: &'a mut W,
+    width: usize,
+
+    /// Without styling, only the layout is rendered, e.g. for pipes.
+    styled: bool,
+
+    /// Whether the last line written was blank, so that runs of blank lines
+    /// are written as one.
+    blank: bool,
+}
+
+impl<W: Write> Renderer<'_, W> {
+    fn set_style(&mut self, style: Style) -> ah::Result<()> {
+        if !self.styled {
+            return Ok(());
+        }
+
+        let attributes = [
+            (style.bold, Attribute::Bold),
+            (style.italic, Attribute::Italic),
+            (style.underlined, Attribute::Underlined),
+            (style.dim, Attribute::Dim),
+        ];
+
+        for (_, attribute) in attributes.iter().filter(|(set, _)| *set) {
+            queue!(self.out, SetAttribute(*attribute))?;
+        }
+
+        if let Some(color) = style.color {
+            queue!(self.out, SetForegroundColor(color))?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str, style: Style) -> ah::Result<()> {
+        if style == Style::default() || text.is_empty() {
+            queue!(self.out, Print(text))?;
+            return Ok(());
+        }
+
+        self.set_style(style)?;
+        queue!(self.out, Print(text))?;
+
+        if self.styled {
+            queue!(self.out, SetAttribute(Attribute::Reset))?;
+        }
+
+        Ok(())
+    }
+
+    fn newline(&mut self) -> ah::Result<()> {
+        queue!(self.out, Print('\n'))?;
+        self.blank = false;
+        Ok(())
+    }
+
+    fn blank_line(&mut self) -> ah::Result<()> {
+        if !self.blank {
+            self.newline()?;
+            self.blank = true;
+        }
+
+        Ok(())
+    }
+
+    fn line(&mut self, prefix: &str, prefix_style: Style, words: &[&Word]) -> ah::Result<()> {
+        self.write(prefix, prefix_style)?;
+
+        for (index, word) in words.iter().enumerate() {
+            if index > 0 {
+                queue!(self.out, Print(' '))?;
+            }
+
+            for (text, style) in word.iter() {
+                self.write(text, *style)?;
+            }
+        }
+
+        self.newline()
+    }
+
+    /// Writes the words, wrapped to the width, with the first line starting
+    /// with the first prefix and every other line with the other one.
+    fn wrapped(
+        &mut self,
+        words: &[Word],
+        first_prefix: &str,
+        prefix: &str,
+        prefix_style: Style,
+    ) -> ah::Result<()> {
+        let mut line: Vec<&Word> = Vec::new();
+        let mut current_prefix = first_prefix;
+        let mut used = first_prefix.chars().count();
+
+        for word in words {
+            let width = word_width(word);
+
+            if !line.is_empty() && used + 1 + width > self.width {
+                self.line(current_prefix, prefix_style, &line)?;
+                line.clear();
+                current_prefix = prefix;
+                used = prefix.chars().count();
+            }
+
+            used += usize::from(!line.is_empty()) + width;
+            line.push(word);
+        }
+
+        self.line(current_prefix, prefix_style, &line)
+    }
+
+    fn flush_pending(&mut self, pending: &mut Option<Pending>) -> ah::Result<()> {
+        let Some(block) = pending.take() else {
+            return Ok(());
+        };
+
+        let prefix_style = match block.quote {
+            true => Style {
+                dim: true,
+                ..Style::default()
+            },
+            false => Style::default(),
+        };
+
+        let words = words(&inline(&block.text, block.style));
+        self.wrapped(&words, &block.first_prefix, &block.prefix, prefix_style)
+    }
+
+    fn table(&mut self, rows: &[Vec<String>]) -> ah::Result<()> {
+        let header = Style {
+            bold: true,
+            ..Style::default()
+        };
+
+        let rendered: Vec<Vec<Vec<(String, Style)>>> = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let base = if index == 0 { header } else { Style::default() };
+                row.iter().map(|cell| inline(cell, base)).collect()
+            })
+            .collect();
+
+        let columns = rendered.iter().map(Vec::len).max().unwrap_or(0);
+
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                rendered
+                    .iter()
+                    .filter_map(|row| row.get(column))
+                    .map(word_width)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let separator = Style {
+            dim: true,
+            ..Style::default()
+        };
+
+        for (index, row) in rendered.iter().enumerate() {
+            for (column, width) in widths.iter().enumerate() {
+                if column > 0 {
+                    self.write(" │ ", separator)?;
+                }
+
+                let cell = row.get(column).cloned().unwrap_or_default();
+
+                for (text, style) in &cell {
+                    self.write(text, *style)?;
+                }
+
+                let padding = width - word_width(&cell);
+
+                if column + 1 < columns {
+                    queue!(self.out, Print(" ".repeat(padding)))?;
+                }
+            }
+
+            self.newline()?;
+
+            if index == 0 && rendered.len() > 1 {
+                let line: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+                self.write(&line.join("─┼─"), separator)?;
+                self.newline()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn document(&mut self, document: &Document) -> ah::Result<()> {
+        let lines = &document.lines;
+        let dim = Style {
+            dim: true,
+            ..Style::default()
+        };
+
+        let mut pending: Option<Pending> = None;
+        let mut table: Vec<Vec<String>> = Vec::new();
+
+        // Nothing is written before the document's first line of content.
+        self.blank = true;
+
+        for (index, (line_num, line, kind)) in lines.iter().enumerate() {
+            let in_table = *kind == BlockKind::Prose && line.trim_start().starts_with('|');
+
+            if !in_table && !table.is_empty() {
+                self.table(&table)?;
+                table.clear();
+            }
+
+            if *kind != BlockKind::Prose {
+                self.flush_pending(&mut pending)?;
+            }
+
+            match kind {
+                // Headings are styled rather than underlined.
+                BlockKind::FrontMatter | BlockKind::Underline => {}
+
+                BlockKind::Blank => self.blank_line()?,
+
+                BlockKind::Heading => {
+                    let Some(heading) = document
+                        .outline
+                        .headings
+                        .iter()
+                        .find(|h| h.line_num == *line_num)
+                    else {
+                        continue;
+                    };
+
+                    let style = match heading.level {
+                        1 => Style {
+                            bold: true,
+                            underlined: true,
+                            color: Some(Color::Magenta),
+                            ..Style::default()
+                        },
+                        2 => Style {
+                            bold: true,
+                            color: Some(Color::Blue),
+                            ..Style::default()
+                        },
+                        _ => Style {
+                            bold: true,
+                            ..Style::default()
+                        },
+                    };
+
+                    let words = words(&inline(&heading_text(line), style));
+                    self.wrapped(&words, "", "", style)?;
+                }
+
+                BlockKind::Fence => {
+                    // Fences alternate between opening and closing blocks.
+                    let opening = lines[..index]
+                        .iter()
+                        .filter(|(_, _, k)| *k == BlockKind::Fence)
+                        .count()
+                        % 2
+                        == 0;
+
+                    match opening {
+                        true => {
+                            let language = fence_language(line);
+                            self.write(format!("┌─ {}", language).trim_end(), dim)?;
+                        }
+                        false => self.write("└─", dim)?,
+                    }
+
+                    self.newline()?;
+                }
+
+                BlockKind::Code => {
+                    self.write("│ ", dim)?;
+                    self.write(line, Style::default())?;
+                    self.newline()?;
+                }
+
+                BlockKind::Prose if in_table => {
+                    let cells = table_cells(line);
+
+                    if !is_table_separator(&cells) {
+                        table.push(cells);
+                    }
+                }
+
+                BlockKind::Prose if is_rule(line) => {
+                    self.flush_pending(&mut pending)?;
+                    self.write(&"─".repeat(self.width.min(80)), dim)?;
+                    self.newline()?;
+                }
+
+                BlockKind::Prose => {
+                    let trimmed = line.trim();
+
+                    if let Some(quoted) = trimmed.strip_prefix('>') {
+                        let quoted = quoted.trim_start();
+
+                        match pending.as_mut().filter(|p| p.quote) {
+                            Some(quote) => {
+                                quote.text.push(' ');
+                                quote.text.push_str(quoted);
+                            }
+                            None => {
+                                self.flush_pending(&mut pending)?;
+
+                                pending = Some(Pending {
+                                    first_prefix: "│ ".to_string(),
+                                    prefix: "│ ".to_string(),
+                                    text: quoted.to_string(),
+                                    style: Style {
+                                        italic: true,
+                                        ..Style::default()
+                                    },
+                                    quote: true,
+                                });
+                            }
+                        }
+                    } else if let Some((indent, marker, text)) = list_item(line) {
+                        self.flush_pending(&mut pending)?;
+
+                        let bullet = match marker {
+                            "-" | "*" | "+" => "•",
+                            numbered => numbered,
+                        };
+
+                        let first_prefix = format!("{}{} ", " ".repeat(indent), bullet);
+                        let prefix = " ".repeat(first_prefix.chars().count());
+
+                        pending = Some(Pending {
+                            first_prefix,
+                            prefix,
+                            text: text.to_string(),
+                            style: Style::default(),
+                            quote: false,
+                        });
+                    } else {
+                        // Lines continue the paragraph, list item or quote
+                        // before them.
+                        match pending.as_mut() {
+                            Some(block) => {
+                                block.text.push(' ');
+                                block.text.push_str(trimmed);
+                            }
+                            None => {
+                                pending = Some(Pending {
+                                    first_prefix: String::new(),
+                                    prefix: String::new(),
+                                    text: trimmed.to_string(),
+                                    style: Style::default(),
+                                    quote: false,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.flush_pending(&mut pending)?;
+
+        if !table.is_empty() {
+            self.table(&table)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders the document's Markdown to the writer, with prose wrapped to the
+/// width, and styled with colors and attributes if styled is set.
+pub fn render(
+    document: &Document,
+    out: &mut impl Write,
+    width: usize,
+    styled: bool,
+) -> ah::Result<()> {
+    let mut renderer = Renderer {
+        out,
+        width: width.max(20),
+        styled,
+        blank: true,
+    };
+
+    renderer.document(document)?;
+    renderer.out.flush().context("render flushing output")?;
+
+    Ok(())
+}
+
+/// Renders the document to stdout, wrapped to the width of the terminal, and
+/// only styled if stdout is one.
+pub fn print(document: &Document) -> ah::Result<()> {
+    let width = Dimensions::from_terminal().map_or(80, |d| d.width());
+    let styled = io::stdout().is_terminal();
+
+    render(document, &mut io::stdout().lock(), width, styled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_render() {
+        let source =
+            "---\nname: Go\n---\n\n# Go\n\nSome **bold** text with `code`, snake_case and a \
+            [link](https://go.dev) that wraps.\n\n- one\n  more\n2. two\n\n> quoted\n\n\
+            | a | bb |\n|---|---|\n| ccc | d |\n\n```go\nx := 1\n```\n***";
+
+        let lines: Vec<(usize, String)> = source.lines().map(String::from).enumerate().collect();
+        let document = Document::parse(Path::new("go.md"), &lines);
+
+        let mut out: Vec<u8> = Vec::new();
+        render(&document, &mut out, 30, false).unwrap();
+
+        let expected = "Go\n\nSome bold text with code,\nsnake_case and a link\n(https://go.dev) that wraps.\n\n\
+            • one more\n2. two\n\n│ quoted\n\na   │ bb\n────┼───\nccc │ d\n\n┌─ go\n│ x := 1\n└─\n\
+            ──────────────────────────────\n";
+
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
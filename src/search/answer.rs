@@ -0,0 +1,262 @@
+use std::io::{self, Write};
+use std::ops::Range;
+
+use anyhow::{self as ah, Context};
+
+use crate::database::document::Document;
+use crate::search::engines::SearchEngine;
+use crate::search::outline::Heading;
+use crate::search::query::{Origin, Query};
+use crate::search::scope::BlockKind;
+
+/// Hits on lines of code weigh this much more than hits in prose, since the
+/// code is usually the answer, and the prose around it only describes it.
+const CODE_WEIGHT: f64 = 2.0;
+
+/// A section of the document, and how well it answers the question.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    /// The heading that opens the section, or None for the lines before the
+    /// first heading.
+    pub heading: Option<Heading>,
+
+    /// The indices of the lines the section spans, its heading included.
+    pub lines: Range<usize>,
+
+    pub score: f64,
+}
+
+/// Splits a question into the terms to look for, dropping the punctuation
+/// around words, e.g. "how do I reverse a list?" into how, do, I, reverse, a
+/// and list. Common words don't need to be filtered out, as they occur in
+/// most sections, which leaves them with next to no weight.
+pub fn question_terms(question: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+
+    for word in question.split_whitespace() {
+        let term = word.trim_matches(|c| matches!(c, '?' | '!' | ',' | '.' | '"' | '\''));
+
+        if !term.is_empty() && !terms.iter().any(|t| t == term) {
+            terms.push(term.to_string());
+        }
+    }
+
+    terms
+}
+
+/// Ranks the sections of the document by TF-IDF over the terms, where the
+/// engine decides whether, and how often, a term occurs on a line. Every
+/// heading opens a section of its own, which ends at the next heading
+/// regardless of its level, and the lines before the first heading make up
+/// one more. Sections that don't contain any of the terms are omitted, and
+/// the rest are ordered from the best answer to the worst.
+pub fn rank_sections<SE: SearchEngine>(
+    engine: &mut SE,
+    document: &Document,
+    terms: &[String],
+    origin: &Origin,
+) -> ah::Result<Vec<Answer>> {
+    let lines = &document.lines;
+
+    let query = Query::And(terms.iter().cloned().map(Query::Term).collect());
+    engine.prepare(&query, origin)?;
+    engine.scan(lines, &query)?;
+
+    let intro = document.blocks.first().map(|block| {
+        let end = document
+            .sections
+            .first()
+            .map_or(lines.len(), |s| s.lines.start);
+
+        (None, block.lines().start..end)
+    });
+
+    let sections: Vec<(Option<Heading>, Range<usize>)> = intro
+        .into_iter()
+        .chain(
+            document
+                .all_sections()
+                .into_iter()
+                .map(|s| (Some(s.heading.clone()), s.own_lines())),
+        )
+        .collect();
+
+    // The weighted number of hits of every term, in every section.
+    let frequencies: Vec<Vec<f64>> = sections
+        .iter()
+        .map(|(_, section)| {
+            terms
+                .iter()
+                .map(|term| {
+                    lines[section.clone()]
+                        .iter()
+                        .filter_map(|(_, line, kind)| {
+                            let (_, spans) = engine.match_term(line, term)?;
+
+                            let weight = match kind {
+                                BlockKind::Code => CODE_WEIGHT,
+                                _ => 1.0,
+                            };
+
+                            Some(spans.len().max(1) as f64 * weight)
+                        })
+                        .sum()
+                })
+                .collect()
+        })
+        .collect();
+
+    // The inverse document frequency as used by BM25. Terms occurring in
+    // every section say next to nothing about which one is the answer, but
+    // still count for something, in case every term of the question does.
+    let count = sections.len() as f64;
+
+    let idf: Vec<f64> = (0..terms.len())
+        .map(|term| {
+            let occurrences = frequencies.iter().filter(|f| f[term] > 0.0).count() as f64;
+            ((count - occurrences + 0.5) / (occurrences + 0.5) + 1.0).ln()
+        })
+        .collect();
+
+    let mut answers: Vec<Answer> = sections
+        .into_iter()
+        .zip(frequencies)
+        .map(|((heading, lines), frequencies)| {
+            let score = frequencies
+                .iter()
+                .zip(&idf)
+                .filter(|(tf, _)| **tf > 0.0)
+                .map(|(tf, idf)| (1.0 + tf.ln()) * idf)
+                .sum();
+
+            Answer {
+                heading,
+                lines,
+                score,
+            }
+        })
+        .filter(|answer| answer.score > 0.0)
+        .collect();
+
+    // Stable, so that sections with equal scores remain in document order.
+    answers.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(answers)
+}
+
+/// Prints the sections verbatim, without the blank lines around them. When
+/// more than one is shown, each gets a header stating where it starts and its
+/// score, like the matches of --find.
+pub fn print_answers(
+    lines: &[(usize, String, BlockKind)],
+    answers: &[Answer],
+    shown: usize,
+) -> ah::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    let shown = match shown {
+        0 => answers.len(),
+        n => n.min(answers.len()),
+    };
+
+    for (index, answer) in answers.iter().take(shown).enumerate() {
+        let section = &lines[answer.lines.clone()];
+
+        let start = section
+            .iter()
+            .position(|(_, _, kind)| *kind != BlockKind::Blank)
+            .unwrap_or(section.len());
+
+        let end = section
+            .iter()
+            .rposition(|(_, _, kind)| *kind != BlockKind::Blank)
+            .map_or(start, |i| i + 1);
+
+        if shown > 1 {
+            if index > 0 {
+                writeln!(stdout)?;
+            }
+
+            writeln!(
+                stdout,
+                "Section {}/{} at line {} (score {:.2}): {}",
+                index + 1,
+                answers.len(),
+                answer.lines.start + 1,
+                answer.score,
+                answer
+                    .heading
+                    .as_ref()
+                    .map_or("(introduction)", |h| h.text.as_str())
+            )?;
+
+            writeln!(stdout, "{}", "-".repeat(80))?;
+        }
+
+        for (_, line, _) in &section[start..end] {
+            writeln!(stdout, "{}", line)?;
+        }
+    }
+
+    stdout.flush().context("print_answers flushing stdout")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::engines::terms::TermSearch;
+    use std::path::Path;
+
+    #[test]
+    fn test_rank_sections() {
+        let source = "---\nname: Python\n---\nIntro, with a list.\n# Lists\nA list holds values.\n\
+            ```python\nli.reverse()\nli[::-1]  # reverse\n```\nTuples\n------\nLike a list, but immutable.";
+
+        let lines: Vec<(usize, String)> = source.lines().map(String::from).enumerate().collect();
+        let document = Document::parse(Path::new("python.md"), &lines);
+
+        let terms = question_terms("How do I reverse a list?");
+        assert_eq!(terms, vec!["How", "do", "I", "reverse", "a", "list"]);
+
+        let answers = rank_sections(
+            &mut TermSearch::default(),
+            &document,
+            &terms,
+            &Origin::default(),
+        )
+        .unwrap();
+
+        let sections: Vec<(Option<&str>, Range<usize>)> = answers
+            .iter()
+            .map(|a| (a.heading.as_ref().map(|h| h.text.as_str()), a.lines.clone()))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                (Some("Lists"), 4..10),
+                (Some("Tuples"), 10..13),
+                (None, 3..4)
+            ]
+        );
+
+        // Back to back headings each open a section of their own.
+        let lines: Vec<(usize, String)> = "# Lists\n## Reversing\nli.reverse()"
+            .lines()
+            .map(String::from)
+            .enumerate()
+            .collect();
+        let document = Document::parse(Path::new("python.md"), &lines);
+
+        let terms = question_terms("lists reversing");
+        let answers = rank_sections(
+            &mut TermSearch::default(),
+            &document,
+            &terms,
+            &Origin::default(),
+        )
+        .unwrap();
+
+        let ranges: Vec<Range<usize>> = answers.into_iter().map(|a| a.lines).collect();
+        assert_eq!(ranges, vec![0..1, 1..3]);
+    }
+}
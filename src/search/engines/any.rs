@@ -0,0 +1,94 @@
+use super::external::ExternalSearch;
+use super::fuzzy::FuzzySearch;
+use super::regex::RegexSearch;
+use super::terms::TermSearch;
+use super::{EngineKind, SearchEngine, Span};
+
+use anyhow as ah;
+
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Origin, Query};
+use crate::search::scope::BlockKind;
+
+/// Any one of the engines, picked at runtime rather than at compile time, so
+/// that it can be swapped for another one, e.g. from within the TUI.
+#[derive(Debug, Clone)]
+pub enum AnyEngine {
+    Terms(TermSearch),
+    Regex(RegexSearch),
+    Fuzzy(FuzzySearch),
+    External(ExternalSearch),
+}
+
+impl Default for AnyEngine {
+    fn default() -> Self {
+        AnyEngine::Terms(TermSearch::default())
+    }
+}
+
+impl AnyEngine {
+    /// Creates an engine of the kind, where the external engine runs the
+    /// search command.
+    pub fn of(kind: EngineKind, normalizer: Normalizer, search_command: &str) -> Self {
+        match kind {
+            EngineKind::Terms => AnyEngine::Terms(TermSearch::new(normalizer)),
+            EngineKind::Regex => AnyEngine::Regex(RegexSearch::new(normalizer)),
+            EngineKind::Fuzzy => AnyEngine::Fuzzy(FuzzySearch::new(normalizer)),
+            EngineKind::External => {
+                AnyEngine::External(ExternalSearch::with_command(normalizer, search_command))
+            }
+        }
+    }
+
+    pub fn kind(&self) -> EngineKind {
+        match self {
+            AnyEngine::Terms(_) => EngineKind::Terms,
+            AnyEngine::Regex(_) => EngineKind::Regex,
+            AnyEngine::Fuzzy(_) => EngineKind::Fuzzy,
+            AnyEngine::External(_) => EngineKind::External,
+        }
+    }
+}
+
+impl SearchEngine for AnyEngine {
+    /// The term engine, as the default one.
+    fn new(normalizer: Normalizer) -> Self {
+        AnyEngine::Terms(TermSearch::new(normalizer))
+    }
+
+    fn match_term(&self, line: &str, term: &str) -> Option<(i64, Vec<Span>)> {
+        match self {
+            AnyEngine::Terms(engine) => engine.match_term(line, term),
+            AnyEngine::Regex(engine) => engine.match_term(line, term),
+            AnyEngine::Fuzzy(engine) => engine.match_term(line, term),
+            AnyEngine::External(engine) => engine.match_term(line, term),
+        }
+    }
+
+    fn prepare(&mut self, query: &Query, origin: &Origin) -> ah::Result<()> {
+        match self {
+            AnyEngine::Terms(engine) => engine.prepare(query, origin),
+            AnyEngine::Regex(engine) => engine.prepare(query, origin),
+            AnyEngine::Fuzzy(engine) => engine.prepare(query, origin),
+            AnyEngine::External(engine) => engine.prepare(query, origin),
+        }
+    }
+
+    fn scan(&mut self, lines: &[(usize, String, BlockKind)], query: &Query) -> ah::Result<()> {
+        match self {
+            AnyEngine::Terms(engine) => engine.scan(lines, query),
+            AnyEngine::Regex(engine) => engine.scan(lines, query),
+            AnyEngine::Fuzzy(engine) => engine.scan(lines, query),
+            AnyEngine::External(engine) => engine.scan(lines, query),
+        }
+    }
+
+    fn required_terms(&self, query: &Query) -> Option<Vec<String>> {
+        match self {
+            AnyEngine::Terms(engine) => engine.required_terms(query),
+            AnyEngine::Regex(engine) => engine.required_terms(query),
+            AnyEngine::Fuzzy(engine) => engine.required_terms(query),
+            AnyEngine::External(engine) => engine.required_terms(query),
+        }
+    }
+}
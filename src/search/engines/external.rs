@@ -0,0 +1,319 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::ops::Range;
+use std::process::{Command, Stdio};
+
+use super::terms::TermSearch;
+use super::{SearchEngine, Span};
+
+use anyhow::{self as ah, Context};
+use serde_json::Value;
+
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Origin, Query};
+use crate::search::scope::BlockKind;
+use crate::utils::shell_stream;
+
+/// The command used unless the search_command config key says otherwise.
+pub const DEFAULT_COMMAND: &str = "rg --json --fixed-strings --regexp";
+
+/// Whether the program of the command can be run at all.
+pub fn available(command: &str) -> bool {
+    let Some(program) = command.split_whitespace().next() else {
+        return false;
+    };
+
+    Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Whether the error is due to the program of the command not existing.
+fn is_not_found(error: &ah::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+/// The one-based number of an input line the command matched, and the byte
+/// ranges of the matches within it, if its output tells.
+type OutputMatch = (usize, Option<Vec<Range<usize>>>);
+
+/// Where a line of output of the command says a term matched. Output that
+/// isn't about a match yields None.
+fn parse_output(output: &str) -> ah::Result<Option<OutputMatch>> {
+    // ripgrep's --json messages.
+    if output.starts_with('{') {
+        let message: Value = serde_json::from_str(output)
+            .with_context(|| format!("ExternalSearch parsing output '{}'", output))?;
+
+        if message["type"] != "match" {
+            return Ok(None);
+        }
+
+        let data = &message["data"];
+
+        let line_number = data["line_number"]
+            .as_u64()
+            .context("ExternalSearch match without a line number")?;
+
+        let ranges = data["submatches"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| Some(m["start"].as_u64()? as usize..m["end"].as_u64()? as usize))
+            .collect();
+
+        return Ok(Some((line_number as usize, Some(ranges))));
+    }
+
+    // Anything else is expected to be grep -n style, i.e. line:text.
+    let line_number = output
+        .split_once(':')
+        .and_then(|(number, _)| number.parse().ok());
+
+    Ok(line_number.map(|number| (number, None)))
+}
+
+/// Delegates matching to an external command, such as ripgrep or grep, for
+/// document sets large enough for it to pay off. Every term is looked for
+/// once per document; the command gets the normalized term as its last
+/// argument, and every normalized line to search on its stdin, and prints the
+/// lines that match either as ripgrep's --json messages, or grep -n style.
+/// Exit codes follow grep's: 0 for matches, 1 for none, and anything else is
+/// an error. If the command can't be run, or for lines it hasn't seen, the
+/// engine falls back to TermSearch, whose semantics it shares. Scanning lines
+/// it has already seen for the same terms doesn't run the command again, so
+/// a document can be scanned once as a whole, and then searched in chunks.
+#[derive(Debug, Clone)]
+pub struct ExternalSearch {
+    normalizer: Normalizer,
+
+    /// The program to run, followed by its arguments.
+    command: Vec<String>,
+
+    /// Where every term matched, by term, then by the line it matched.
+    hits: HashMap<String, HashMap<String, Vec<Range<usize>>>>,
+
+    /// The distinct terms of the last scan, sorted.
+    terms: Vec<String>,
+
+    /// Every line the command has seen since the last scan.
+    scanned: HashSet<String>,
+
+    /// Set once the command turned out to be missing.
+    missing: bool,
+
+    fallback: TermSearch,
+}
+
+impl Default for ExternalSearch {
+    fn default() -> Self {
+        Self::new(Normalizer::default())
+    }
+}
+
+impl ExternalSearch {
+    pub fn with_command(normalizer: Normalizer, command: &str) -> Self {
+        Self {
+            normalizer,
+            command: command.split_whitespace().map(String::from).collect(),
+            hits: HashMap::new(),
+            terms: Vec::new(),
+            scanned: HashSet::new(),
+            missing: false,
+            fallback: TermSearch::new(normalizer),
+        }
+    }
+
+    /// Runs the command for a single term over the lines, returning the lines
+    /// it matched, along with the byte ranges of the matches.
+    fn run(
+        &self,
+        lines: &[(usize, String, BlockKind)],
+        term: &str,
+    ) -> ah::Result<HashMap<String, Vec<Range<usize>>>> {
+        let (program, arguments) = self
+            .command
+            .split_first()
+            .context("ExternalSearch::run empty search command")?;
+
+        let fold_case = self.normalizer.folds_case(term);
+        let term = self.normalizer.normalize(term, fold_case).text;
+
+        let normalized: Vec<_> = lines
+            .iter()
+            .map(|(_, line, _)| self.normalizer.normalize(line, fold_case))
+            .collect();
+
+        let input: String = normalized.iter().map(|n| n.text.clone() + "\n").collect();
+
+        let mut arguments: Vec<&str> = arguments.iter().map(String::as_str).collect();
+        arguments.push(&term);
+
+        let mut found: HashMap<String, Vec<Range<usize>>> = HashMap::new();
+
+        let (code, stderr) = shell_stream(program, arguments, &input, |output| {
+            let Some((number, ranges)) = parse_output(output)? else {
+                return Ok(());
+            };
+
+            let Some(index) = number.checked_sub(1).filter(|&i| i < lines.len()) else {
+                return Ok(());
+            };
+
+            let normalized = &normalized[index];
+
+            // Without ranges in the output, the term is found again.
+            let ranges = ranges.unwrap_or_else(|| {
+                normalized
+                    .text
+                    .match_indices(&term)
+                    .map(|(start, _)| start..start + term.len())
+                    .collect()
+            });
+
+            found
+                .entry(lines[index].1.clone())
+                .or_default()
+                .extend(ranges.into_iter().map(|r| normalized.original_range(r)));
+
+            Ok(())
+        })?;
+
+        match code {
+            Some(0) | Some(1) => Ok(found),
+            _ => ah::bail!(
+                "ExternalSearch::run {} exited with {}: {}",
+                program,
+                code.map_or("a signal".to_string(), |c| format!("code {}", c)),
+                stderr.trim()
+            ),
+        }
+    }
+}
+
+impl SearchEngine for ExternalSearch {
+    /// Creates an engine running the default command; see with_command.
+    fn new(normalizer: Normalizer) -> Self {
+        Self::with_command(normalizer, DEFAULT_COMMAND)
+    }
+
+    fn prepare(&mut self, query: &Query, origin: &Origin) -> ah::Result<()> {
+        self.fallback.prepare(query, origin)
+    }
+
+    fn scan(&mut self, lines: &[(usize, String, BlockKind)], query: &Query) -> ah::Result<()> {
+        let mut terms: Vec<String> = query.terms().into_iter().map(String::from).collect();
+        terms.sort_unstable();
+        terms.dedup();
+
+        if terms == self.terms && lines.iter().all(|(_, line, _)| self.scanned.contains(line)) {
+            return Ok(());
+        }
+
+        self.hits.clear();
+        self.scanned.clear();
+        self.terms.clear();
+
+        if self.missing {
+            return Ok(());
+        }
+
+        for term in &terms {
+            match self.run(lines, term) {
+                Ok(found) => {
+                    self.hits.insert(term.clone(), found);
+                }
+
+                Err(e) if is_not_found(&e) => {
+                    self.missing = true;
+                    self.hits.clear();
+                    return Ok(());
+                }
+
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.scanned = lines.iter().map(|(_, line, _)| line.clone()).collect();
+        self.terms = terms;
+        Ok(())
+    }
+
+    fn match_term(&self, line: &str, term: &str) -> Option<(i64, Vec<Span>)> {
+        let hits = self.hits.get(term).filter(|_| self.scanned.contains(line));
+
+        let Some(hits) = hits else {
+            return self.fallback.match_term(line, term);
+        };
+
+        let mut spans: Vec<Span> = hits
+            .get(line)?
+            .iter()
+            .map(|range| Span::from_bytes(line, range.clone(), term))
+            .collect();
+
+        spans.sort_by_key(|s| s.bytes.start);
+        spans.dedup();
+
+        Some((spans.len().max(1) as i64, spans))
+    }
+
+    fn required_terms(&self, query: &Query) -> Option<Vec<String>> {
+        self.fallback.required_terms(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::scope::tag_lines;
+
+    #[test]
+    fn test_external_search() {
+        let lines: Vec<(usize, String)> = ["Defer a call", "no match", "defer, DEFER"]
+            .iter()
+            .map(|s| s.to_string())
+            .enumerate()
+            .collect();
+        let tagged = tag_lines(&lines);
+        let query = Query::parse("defer").unwrap();
+
+        let mut grep = ExternalSearch::with_command(Normalizer::default(), "grep -n -F -e");
+        let matches = grep.search(&tagged, &query, &Origin::default()).unwrap();
+        let rows: Vec<(usize, usize)> = matches
+            .iter()
+            .map(|m| (m.line_num, m.spans.len()))
+            .collect();
+        assert_eq!(rows, vec![(2, 2), (0, 1)]);
+
+        let mut missing = ExternalSearch::with_command(Normalizer::default(), "xiny-missing -e");
+        let fallback = missing.search(&tagged, &query, &Origin::default()).unwrap();
+        assert_eq!(fallback, matches);
+
+        let message = r#"{"type":"match","data":{"line_number":3,"submatches":[{"start":0,"end":5},{"start":7,"end":12}]}}"#;
+        assert_eq!(
+            parse_output(message).unwrap(),
+            Some((3, Some(vec![0..5, 7..12])))
+        );
+        assert_eq!(parse_output(r#"{"type":"begin","data":{}}"#).unwrap(), None);
+
+        // Lines that were already scanned for the same terms are searched
+        // without running the command again.
+        grep.command = vec!["false".to_string()];
+        let chunk = grep.search(&tagged[2..], &query, &Origin::default()).unwrap();
+        assert_eq!(chunk.len(), 1);
+        assert_eq!(chunk[0].spans.len(), 2);
+
+        let failing = ExternalSearch::with_command(Normalizer::default(), "grep --no-such-flag");
+        assert!(failing
+            .clone()
+            .search(&tagged, &query, &Origin::default())
+            .is_err());
+    }
+}
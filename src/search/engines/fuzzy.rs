@@ -1 +1,334 @@
+use std::cmp::Reverse;
 
+use super::{SearchEngine, Span};
+
+use crate::search::normalize::Normalizer;
+
+// Scoring constants, modelled after fzf. Every matched character is worth
+// SCORE_MATCH, gaps between matched characters are penalized, and matches on
+// "interesting" characters, e.g. the start of a word, receive a bonus.
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+
+const BONUS_BOUNDARY_WHITE: i32 = 10;
+const BONUS_BOUNDARY_DELIMITER: i32 = 9;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_NON_WORD: i32 = 8;
+const BONUS_CAMEL: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = -(SCORE_GAP_START + SCORE_GAP_EXTENSION);
+
+/// The bonus of the first query character is multiplied by this, since where
+/// the match starts says the most about how relevant it is.
+const BONUS_FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+/// Used in place of a score for cells of the matrix that cannot be reached.
+const UNREACHABLE: i32 = i32::MIN / 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    White,
+    Delimiter,
+    NonWord,
+    Lower,
+    Upper,
+    Number,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        match c {
+            c if c.is_whitespace() => CharClass::White,
+            '/' | ',' | ':' | ';' | '|' | '_' | '-' | '.' => CharClass::Delimiter,
+            c if c.is_numeric() => CharClass::Number,
+            c if c.is_uppercase() => CharClass::Upper,
+            c if c.is_alphabetic() => CharClass::Lower,
+            _ => CharClass::NonWord,
+        }
+    }
+
+    fn is_word(&self) -> bool {
+        matches!(
+            self,
+            CharClass::Lower | CharClass::Upper | CharClass::Number
+        )
+    }
+}
+
+/// The bonus for matching a character of the class `current`, when preceded
+/// by a character of the class `previous`.
+fn bonus(previous: CharClass, current: CharClass) -> i32 {
+    if current.is_word() {
+        match previous {
+            CharClass::White => BONUS_BOUNDARY_WHITE,
+            CharClass::Delimiter => BONUS_BOUNDARY_DELIMITER,
+            CharClass::NonWord => BONUS_BOUNDARY,
+            CharClass::Lower if current == CharClass::Upper => BONUS_CAMEL,
+            CharClass::Lower | CharClass::Upper if current == CharClass::Number => BONUS_CAMEL,
+            _ => 0,
+        }
+    } else if matches!(current, CharClass::NonWord | CharClass::Delimiter) {
+        BONUS_NON_WORD
+    } else {
+        0
+    }
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// The result of fuzzy matching a query against a single line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+
+    /// The char (not byte) index of every character in the line that was
+    /// matched by a character of the query, in ascending order.
+    pub positions: Vec<usize>,
+}
+
+/// Scores the query as a subsequence of the line, choosing the alignment with
+/// the highest score. If fold_case is set, the characters of the line are
+/// lowercased before being compared to those of the query, which should be
+/// lowercase already. Returns None if the query is not a subsequence of the
+/// line at all.
+pub fn fuzzy_match(query: &[char], line: &str, fold_case: bool) -> Option<FuzzyMatch> {
+    if query.is_empty() {
+        return None;
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+
+    let folded: Vec<char> = match fold_case {
+        true => chars.iter().copied().map(fold).collect(),
+        false => chars.clone(),
+    };
+
+    // Cheap subsequence check before building the matrices, since the vast
+    // majority of lines won't match at all.
+    let mut remaining = query.iter().peekable();
+    for c in &folded {
+        if remaining.peek() == Some(&c) {
+            remaining.next();
+        }
+    }
+    if remaining.peek().is_some() {
+        return None;
+    }
+
+    let (m, n) = (query.len(), chars.len());
+
+    let bonuses: Vec<i32> = (0..n)
+        .map(|j| {
+            let previous = match j {
+                0 => CharClass::White,
+                j => CharClass::of(chars[j - 1]),
+            };
+
+            bonus(previous, CharClass::of(chars[j]))
+        })
+        .collect();
+
+    // matched[i][j]: best score where query[i] is matched exactly at line[j].
+    // gapped[i][j]: best score where query[..=i] is matched within line[..j],
+    // but line[j] itself is left unmatched.
+    let mut matched = vec![vec![UNREACHABLE; n]; m];
+    let mut gapped = vec![vec![UNREACHABLE; n]; m];
+
+    // The largest bonus seen within the consecutive run ending at matched[i][j].
+    // The whole run is awarded it, so that "main" matched at the start of a
+    // word beats the same characters scattered across several words.
+    let mut run_bonus = vec![vec![0; n]; m];
+
+    // Whether matched[i][j] was reached from a consecutive match (true) or
+    // from a gap (false), and likewise whether gapped[i][j] opened the gap.
+    let mut from_consecutive = vec![vec![false; n]; m];
+    let mut gap_opened = vec![vec![false; n]; m];
+
+    for i in 0..m {
+        for j in i..n {
+            if j > 0 {
+                let open = matched[i][j - 1].saturating_add(SCORE_GAP_START);
+                let extend = gapped[i][j - 1].saturating_add(SCORE_GAP_EXTENSION);
+
+                gap_opened[i][j] = open >= extend;
+                gapped[i][j] = open.max(extend);
+            }
+
+            if folded[j] != query[i] {
+                continue;
+            }
+
+            if i == 0 {
+                matched[i][j] = SCORE_MATCH + bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER;
+                run_bonus[i][j] = bonuses[j];
+                continue;
+            }
+
+            if j == 0 {
+                continue;
+            }
+
+            let carried = bonuses[j].max(run_bonus[i - 1][j - 1]);
+
+            let consecutive =
+                matched[i - 1][j - 1].saturating_add(SCORE_MATCH + carried.max(BONUS_CONSECUTIVE));
+
+            let after_gap = gapped[i - 1][j - 1].saturating_add(SCORE_MATCH + bonuses[j]);
+
+            from_consecutive[i][j] = consecutive >= after_gap;
+            matched[i][j] = consecutive.max(after_gap);
+            run_bonus[i][j] = if from_consecutive[i][j] {
+                carried
+            } else {
+                bonuses[j]
+            };
+        }
+    }
+
+    // Trailing unmatched characters aren't penalized, so the best alignment
+    // can end anywhere. Prefer the earliest end on equal scores.
+    let (mut j, score) = (0..n)
+        .map(|j| (j, matched[m - 1][j]))
+        .filter(|(_, score)| *score > UNREACHABLE)
+        .max_by_key(|(j, score)| (*score, Reverse(*j)))?;
+
+    let mut positions = vec![0; m];
+    let mut i = m - 1;
+    let mut in_gap = false;
+
+    loop {
+        if in_gap {
+            in_gap = !gap_opened[i][j];
+            j -= 1;
+            continue;
+        }
+
+        positions[i] = j;
+
+        if i == 0 {
+            break;
+        }
+
+        in_gap = !from_consecutive[i][j];
+        i -= 1;
+        j -= 1;
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// Every term of the query is matched as a subsequence of the line, the same
+/// way fzf matches each of its space separated terms. Lines are ranked by how
+/// well the terms align with them, favouring word boundaries, camelCase humps
+/// and consecutive runs of matched characters.
+///
+/// The line is normalized without case folding it, as the bonuses depend on
+/// the case of its characters, so case insensitive terms are matched by
+/// lowercasing one character at a time instead.
+#[derive(Debug, Clone, Default)]
+pub struct FuzzySearch {
+    normalizer: Normalizer,
+}
+
+impl SearchEngine for FuzzySearch {
+    fn new(normalizer: Normalizer) -> Self {
+        Self { normalizer }
+    }
+
+    fn match_term(&self, line: &str, term: &str) -> Option<(i64, Vec<Span>)> {
+        let fold_case = self.normalizer.folds_case(term);
+        let normalized = self.normalizer.normalize(line, false);
+
+        let query: Vec<char> = self
+            .normalizer
+            .normalize(term, false)
+            .text
+            .chars()
+            .map(|c| if fold_case { fold(c) } else { c })
+            .collect();
+
+        let found = fuzzy_match(&query, &normalized.text, fold_case)?;
+
+        // The byte offset of every char of the normalized line, and its end.
+        let offsets: Vec<usize> = normalized
+            .text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([normalized.text.len()])
+            .collect();
+
+        // Merge runs of consecutive positions into a single span.
+        let mut runs: Vec<std::ops::Range<usize>> = Vec::new();
+
+        for &position in &found.positions {
+            match runs.last_mut() {
+                Some(run) if run.end == position => run.end += 1,
+                _ => runs.push(position..position + 1),
+            }
+        }
+
+        let spans = runs
+            .into_iter()
+            .map(|run| {
+                let bytes = normalized.original_range(offsets[run.start]..offsets[run.end]);
+                Span::from_bytes(line, bytes, term)
+            })
+            .collect();
+
+        Some((found.score as i64, spans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::engines::Match;
+    use crate::search::query::{Origin, Query};
+    use crate::search::scope::tag_lines;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match(&chars("xyz"), "abc", true), None);
+        assert_eq!(fuzzy_match(&chars(""), "abc", true), None);
+
+        let found = fuzzy_match(&chars("lc"), "list comprehension", true).unwrap();
+        assert_eq!(found.positions, vec![0, 5]);
+
+        // Prefers the consecutive run over the earlier scattered characters.
+        let found = fuzzy_match(&chars("main"), "m a i n main", true).unwrap();
+        assert_eq!(found.positions, vec![8, 9, 10, 11]);
+
+        // camelCase humps and word boundaries beat matches mid-word.
+        let boundary = fuzzy_match(&chars("gs"), "getString", true).unwrap();
+        let mid_word = fuzzy_match(&chars("gs"), "bigness", true).unwrap();
+        assert!(boundary.score > mid_word.score);
+    }
+
+    #[test]
+    fn test_fuzzy_search() {
+        let lines: Vec<(usize, String)> = ["nothing here", "reverse a list", "rev_list()"]
+            .iter()
+            .map(|s| s.to_string())
+            .enumerate()
+            .collect();
+
+        let query = Query::parse("rev list").unwrap();
+        let results = FuzzySearch::default()
+            .search(&tag_lines(&lines), &query, &Origin::default())
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        // Each term is matched on its own, so both lines match every term at
+        // the start of a word, but whitespace is the stronger word boundary.
+        let spans = |m: &Match| -> Vec<_> { m.spans.iter().map(|s| s.chars.clone()).collect() };
+        assert_eq!(results[0].line_num, 1);
+        assert_eq!(spans(&results[0]), vec![0..3, 10..14]);
+        assert_eq!(spans(&results[1]), vec![0..3, 4..8]);
+    }
+}
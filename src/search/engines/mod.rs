@@ -1,10 +1,340 @@
+use std::cmp::Reverse;
 use std::fmt::Debug;
+use std::ops::Range;
 
+use anyhow as ah;
+
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Origin, Query};
+use crate::search::scope::BlockKind;
+
+pub mod any;
+pub mod external;
 pub mod fuzzy;
 pub mod regex;
 pub mod terms;
 
+/// The search engines to choose from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EngineKind {
+    #[default]
+    Terms,
+    Regex,
+    Fuzzy,
+    External,
+}
+
+impl EngineKind {
+    /// The engine by its name in the engine config key, e.g. regex.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "terms" => Some(EngineKind::Terms),
+            "regex" => Some(EngineKind::Regex),
+            "fuzzy" => Some(EngineKind::Fuzzy),
+            "external" => Some(EngineKind::External),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineKind::Terms => "terms",
+            EngineKind::Regex => "regex",
+            EngineKind::Fuzzy => "fuzzy",
+            EngineKind::External => "external",
+        }
+    }
+
+    /// The engine after this one when cycling through them: terms, regex,
+    /// fuzzy, and back to terms. The external engine leads back to terms too,
+    /// as it's only ever used when asked for.
+    pub fn next(&self) -> Self {
+        match self {
+            EngineKind::Terms => EngineKind::Regex,
+            EngineKind::Regex => EngineKind::Fuzzy,
+            EngineKind::Fuzzy | EngineKind::External => EngineKind::Terms,
+        }
+    }
+}
+
+/// A contiguous part of a line that was matched by a term of the query.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    /// The zero-based line number of the line the span is on. Engines only
+    /// match single lines, so it's filled in once the match is assembled.
+    pub line_num: usize,
+
+    /// Byte offsets into the line, suitable for slicing it.
+    pub bytes: Range<usize>,
+
+    /// Char offsets into the line, suitable for column numbers and drawing.
+    pub chars: Range<usize>,
+
+    /// The term of the query that produced this span.
+    pub term: String,
+}
+
+impl Span {
+    /// Creates a span from a byte range of the line, deriving the char range.
+    pub fn from_bytes(line: &str, bytes: Range<usize>, term: &str) -> Self {
+        let start = line[..bytes.start].chars().count();
+        let len = line[bytes.clone()].chars().count();
+
+        Self {
+            line_num: 0,
+            chars: start..start + len,
+            bytes,
+            term: term.to_string(),
+        }
+    }
+
+    /// Creates a span from a char range of the line, deriving the byte range.
+    pub fn from_chars(line: &str, chars: Range<usize>, term: &str) -> Self {
+        let byte_at = |c: usize| line.char_indices().nth(c).map_or(line.len(), |(b, _)| b);
+
+        Self {
+            line_num: 0,
+            bytes: byte_at(chars.start)..byte_at(chars.end),
+            chars,
+            term: term.to_string(),
+        }
+    }
+}
+
+/// A line that matched the query, along with where and how well it matched.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Match {
+    /// The zero-based line number within the document.
+    pub line_num: usize,
+
+    /// The zero-based line number of the last line of the match, which is
+    /// line_num itself, unless the match spans a window of lines (--near).
+    pub end_line: usize,
+
+    /// How relevant the match is; higher scores are more relevant. Scores
+    /// are only comparable between matches produced by the same engine.
+    pub score: i64,
+
+    /// The matched parts of the lines, ordered by their position in them.
+    pub spans: Vec<Span>,
+
+    /// The headings enclosing the match, outermost first, e.g. Rust, Traits
+    /// and Generic bounds. Empty until attached by an Outline.
+    pub breadcrumb: Vec<String>,
+}
+
+impl Match {
+    /// Whether the line is within the lines the match spans.
+    pub fn contains_line(&self, line_num: usize) -> bool {
+        (self.line_num..=self.end_line).contains(&line_num)
+    }
+
+    /// The matched parts of a single line of the match.
+    pub fn spans_on(&self, line_num: usize) -> Vec<Span> {
+        self.spans
+            .iter()
+            .filter(|s| s.line_num == line_num)
+            .cloned()
+            .collect()
+    }
+
+    /// The byte offset of the first matched span on the first line of the
+    /// match, or 0 if there are none.
+    pub fn col(&self) -> usize {
+        self.spans
+            .first()
+            .filter(|s| s.line_num == self.line_num)
+            .map_or(0, |s| s.bytes.start)
+    }
+
+    /// Every distinct term that produced a span, in order of appearance.
+    pub fn terms(&self) -> Vec<&str> {
+        let mut terms: Vec<&str> = Vec::new();
+
+        for span in &self.spans {
+            if !terms.contains(&span.term.as_str()) {
+                terms.push(&span.term);
+            }
+        }
+
+        terms
+    }
+}
+
+/// Sorts matches from most to least relevant. The sort is stable, so matches
+/// with equal scores remain in document order.
+pub fn sort_by_relevance(matches: &mut [Match]) {
+    matches.sort_by_key(|m| Reverse(m.score));
+}
+
+/// An engine decides how a single term of a query matches a line, and how
+/// relevant that match is; the query decides how the terms are combined.
 pub trait SearchEngine: Debug + Clone + Default {
-    /// Search through every line for query, return (row,col) for every match.
-    fn search(&mut self, lines: &Vec<(usize, String)>, query: &str) -> Vec<(usize, usize)>;
+    /// Creates an engine that normalizes both the terms and the lines with
+    /// the normalizer before matching them.
+    fn new(normalizer: Normalizer) -> Self;
+
+    /// Matches a single term against the line, returning a score and the
+    /// matched spans, or None if the term doesn't match the line.
+    fn match_term(&self, line: &str, term: &str) -> Option<(i64, Vec<Span>)>;
+
+    /// Prepares the engine for matching the terms of the query against lines
+    /// of a document of the origin, e.g. by compiling them. Fails if a term is
+    /// malformed for this engine.
+    fn prepare(&mut self, _query: &Query, _origin: &Origin) -> ah::Result<()> {
+        Ok(())
+    }
+
+    /// Lets the engine see every line about to be matched against the terms
+    /// of the query before any of them is, e.g. to hand all of them to an
+    /// external program at once. Called after prepare.
+    fn scan(&mut self, _lines: &[(usize, String, BlockKind)], _query: &Query) -> ah::Result<()> {
+        Ok(())
+    }
+
+    /// Search through every line for query, returning every matching line
+    /// ordered from most to least relevant. Fails if the query is malformed.
+    fn search(
+        &mut self,
+        lines: &[(usize, String, BlockKind)],
+        query: &Query,
+        origin: &Origin,
+    ) -> ah::Result<Vec<Match>> {
+        self.prepare(query, origin)?;
+        self.scan(lines, query)?;
+
+        let mut matches: Vec<Match> = lines
+            .iter()
+            .filter_map(|(line_num, line, kind)| {
+                let (score, mut spans) = query.evaluate(self, line, *kind, origin)?;
+                spans.sort_by_key(|s| s.bytes.start);
+
+                for span in &mut spans {
+                    span.line_num = *line_num;
+                }
+
+                Some(Match {
+                    line_num: *line_num,
+                    end_line: *line_num,
+                    score,
+                    spans,
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        sort_by_relevance(&mut matches);
+        Ok(matches)
+    }
+
+    /// Searches for windows of up to `near` lines that satisfy the query
+    /// together, rather than single lines, e.g. a comment and the code below
+    /// it. Every window is as small as it can be, and of windows overlapping
+    /// each other only the best is kept. Tighter windows rank highest, then
+    /// those with higher scores. Fails if the query is malformed.
+    fn search_near(
+        &mut self,
+        lines: &[(usize, String, BlockKind)],
+        query: &Query,
+        origin: &Origin,
+        near: usize,
+    ) -> ah::Result<Vec<Match>> {
+        self.prepare(query, origin)?;
+        self.scan(lines, query)?;
+
+        let mut terms: Vec<&str> = query.terms();
+        terms.sort_unstable();
+        terms.dedup();
+
+        // How every term matches every line, so that windows sharing lines
+        // don't have to match them again.
+        let hits: Vec<Vec<_>> = lines
+            .iter()
+            .map(|(line_num, line, _)| {
+                terms
+                    .iter()
+                    .map(|term| {
+                        let (score, mut spans) = self.match_term(line, term)?;
+
+                        for span in &mut spans {
+                            span.line_num = *line_num;
+                        }
+
+                        Some((score, spans))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let evaluate = |window: Range<usize>| {
+            query.evaluate_with(origin, &|term, required| {
+                let term = terms.binary_search(&term).ok()?;
+                let mut found: Option<(i64, Vec<Span>)> = None;
+
+                for index in window.clone() {
+                    if required.is_some_and(|kind| kind != lines[index].2) {
+                        continue;
+                    }
+
+                    if let Some((score, spans)) = &hits[index][term] {
+                        let (total, all) = found.get_or_insert_with(Default::default);
+                        *total += score;
+                        all.extend(spans.iter().cloned());
+                    }
+                }
+
+                found
+            })
+        };
+
+        let mut windows: Vec<Match> = Vec::new();
+
+        for start in 0..lines.len() {
+            // A line without any hits can't start the smallest window.
+            if hits[start].iter().all(Option::is_none) {
+                continue;
+            }
+
+            let first = lines[start].0;
+
+            let end = (start..lines.len())
+                .take_while(|end| lines[*end].0 < first + near.max(1))
+                .find_map(|end| Some((end, evaluate(start..end + 1)?)));
+
+            if let Some((end, (score, mut spans))) = end {
+                spans.sort_by_key(|s| (s.line_num, s.bytes.start));
+
+                windows.push(Match {
+                    line_num: first,
+                    end_line: lines[end].0,
+                    score,
+                    spans,
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Stable, so that equally ranked windows remain in document order.
+        windows.sort_by_key(|m| (m.end_line - m.line_num, Reverse(m.score)));
+
+        let mut matches: Vec<Match> = Vec::new();
+
+        for window in windows {
+            if !matches
+                .iter()
+                .any(|m| m.line_num <= window.end_line && window.line_num <= m.end_line)
+            {
+                matches.push(window);
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Substrings that every line matching the query must contain, which lets
+    /// an Index narrow down the lines worth searching. None if the engine can't
+    /// tell, in which case every line has to be searched.
+    fn required_terms(&self, _query: &Query) -> Option<Vec<String>> {
+        None
+    }
 }
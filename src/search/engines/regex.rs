@@ -1 +1,103 @@
+use std::collections::HashMap;
 
+use super::{SearchEngine, Span};
+
+use anyhow::{self as ah, Context};
+use regex::{Regex, RegexBuilder};
+
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Origin, Query};
+
+/// Treats every term of the query as a regular expression, matched against
+/// the normalized line. Case insensitivity is left to the regex engine, since
+/// case folding the expressions themselves would change what they mean, e.g.
+/// \S into \s. Lines where the expressions matched less content are
+/// considered more relevant.
+#[derive(Debug, Clone, Default)]
+pub struct RegexSearch {
+    normalizer: Normalizer,
+
+    /// Every expression compiled so far, by the term it was compiled from,
+    /// so that searching the same terms again does not recompile them.
+    expressions: HashMap<String, Regex>,
+}
+
+impl SearchEngine for RegexSearch {
+    fn new(normalizer: Normalizer) -> Self {
+        Self {
+            normalizer,
+            ..Default::default()
+        }
+    }
+
+    fn prepare(&mut self, query: &Query, _origin: &Origin) -> ah::Result<()> {
+        for term in query.terms() {
+            if self.expressions.contains_key(term) {
+                continue;
+            }
+
+            let expression = RegexBuilder::new(term)
+                .case_insensitive(self.normalizer.folds_case(term))
+                .build()
+                .with_context(|| format!("Invalid regular expression '{}'", term))?;
+
+            self.expressions.insert(term.to_string(), expression);
+        }
+
+        Ok(())
+    }
+
+    fn match_term(&self, line: &str, term: &str) -> Option<(i64, Vec<Span>)> {
+        let re = self.expressions.get(term)?;
+        let normalized = self.normalizer.normalize(line, false);
+
+        // The tighter the expression matched, the more relevant.
+        let length = re.find(&normalized.text)?.len();
+
+        let spans: Vec<Span> = re
+            .find_iter(&normalized.text)
+            .filter(|m| !m.is_empty())
+            .map(|m| Span::from_bytes(line, normalized.original_range(m.range()), term))
+            .collect();
+
+        Some((-(length as i64), spans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::engines::Match;
+    use crate::search::query::Origin;
+    use crate::search::scope::tag_lines;
+
+    fn search(
+        engine: &mut RegexSearch,
+        lines: &[(usize, String)],
+        query: &str,
+    ) -> ah::Result<Vec<Match>> {
+        engine.search(&tag_lines(lines), &Query::parse(query)?, &Origin::default())
+    }
+
+    #[test]
+    fn test_regex_search() {
+        let lines: Vec<(usize, String)> = ["fn main() {}", "fn  main", "let x = 1;", "FN MAIN"]
+            .iter()
+            .map(|s| s.to_string())
+            .enumerate()
+            .collect();
+
+        let mut engine = RegexSearch::default();
+
+        let results = search(&mut engine, &lines, r"fn\s+main").unwrap();
+        let rows: Vec<usize> = results.iter().map(|m| m.line_num).collect();
+        assert_eq!(rows, vec![0, 3, 1]);
+        assert_eq!(results[0].spans[0].bytes, 0..7);
+
+        let results = search(&mut engine, &lines, r"fn \d").unwrap();
+        assert!(results.is_empty());
+
+        assert!(search(&mut engine, &lines, "fn(").is_err());
+        assert!(search(&mut engine, &lines, "").is_err());
+    }
+}
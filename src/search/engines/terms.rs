@@ -1,44 +1,100 @@
-use super::SearchEngine;
+use std::ops::Range;
 
+use super::{SearchEngine, Span};
+
+use anyhow as ah;
+
+use crate::search::index::NGRAM;
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Origin, Query};
+use crate::search::stem::{words, Stemmer};
+
+/// Matches every term as a substring of the line, once both are normalized.
+/// When stemming, the words of a term also match words of the line with the
+/// same stems. Lines containing the terms the most often rank highest.
 #[derive(Debug, Clone, Default)]
 pub struct TermSearch {
-    x: u32,
+    normalizer: Normalizer,
+
+    /// The stemmer for the language of the document being searched, if
+    /// stemming and the language has one.
+    stemmer: Option<Stemmer>,
 }
 
 impl SearchEngine for TermSearch {
-    /// Search for the query in the given lines and return the line number and index of the first match.
-    fn search(&mut self, lines: &Vec<(usize, String)>, query: &str) -> Vec<(usize, usize)> {
-        let query = query.to_ascii_lowercase();
-        let _terms = query.split_whitespace().collect::<Vec<&str>>();
-
-        lines
-            .iter()
-            .fold(Vec::<(usize, usize)>::new(), |mut acc, (line_num, line)| {
-                let line = line.to_ascii_lowercase();
-
-                // for term in &terms {
-                    if line.contains(&query) {
-                        acc.push((*line_num, 0));
-                    }
-                // }
-
-                acc
+    fn new(normalizer: Normalizer) -> Self {
+        Self {
+            normalizer,
+            stemmer: None,
+        }
+    }
+
+    fn prepare(&mut self, _query: &Query, origin: &Origin) -> ah::Result<()> {
+        self.stemmer = match self.normalizer.stem {
+            true => Stemmer::for_tag(&origin.lang),
+            false => None,
+        };
+
+        Ok(())
+    }
+
+    fn match_term(&self, line: &str, term: &str) -> Option<(i64, Vec<Span>)> {
+        let fold_case = self.normalizer.folds_case(term);
+        let term = self.normalizer.normalize(term, fold_case).text;
+        let normalized = self.normalizer.normalize(line, fold_case);
+
+        let mut spans: Vec<Span> = normalized
+            .text
+            .match_indices(&term)
+            .map(|(start, _)| {
+                let bytes = normalized.original_range(start..start + term.len());
+                Span::from_bytes(line, bytes, &term)
             })
+            .collect();
+
+        let stemmer = self.stemmer.filter(|_| words(&term).next().is_some());
+
+        if let Some(stemmer) = stemmer {
+            let stems: Vec<String> = words(&term).map(|(_, word)| stemmer.stem(word)).collect();
+
+            let line_words: Vec<(Range<usize>, String)> = words(&normalized.text)
+                .map(|(range, word)| (range, stemmer.stem(word)))
+                .collect();
+
+            // The words of a phrase have to occur in the same order, without
+            // other words in between.
+            for window in line_words.windows(stems.len()) {
+                if !window.iter().map(|(_, stem)| stem).eq(&stems) {
+                    continue;
+                }
+
+                let range = window[0].0.start..window[window.len() - 1].0.end;
+                let bytes = normalized.original_range(range);
+
+                // Words already matched as a substring aren't matched again.
+                if !spans
+                    .iter()
+                    .any(|s| s.bytes.start < bytes.end && bytes.start < s.bytes.end)
+                {
+                    spans.push(Span::from_bytes(line, bytes, &term));
+                }
+            }
+
+            spans.sort_by_key(|s| s.bytes.start);
+        }
+
+        (!spans.is_empty()).then_some((spans.len() as i64, spans))
+    }
 
-        // let terms = query.split_whitespace().collect::<Vec<&str>>();
-        //
-        // lines
-        //     .iter()
-        //     .fold(Vec::<(usize, usize)>::new(), |mut acc, (line_num, line)| {
-        //         for term in &terms {
-        //             if let Some(index) = line.find(term) {
-        //                 acc.push((*line_num, index));
-        //                 return acc;
-        //             }
-        //         }
-        //
-        //         acc
-        //     })
+    /// Every token of a required term has to occur within some token of the
+    /// line. Tokens too short for an Index to look up efficiently are left out.
+    fn required_terms(&self, query: &Query) -> Option<Vec<String>> {
+        let terms: Vec<String> = query
+            .required_tokens()
+            .into_iter()
+            .filter(|token| token.chars().count() >= NGRAM)
+            .collect();
+        (!terms.is_empty()).then_some(terms)
     }
 }
 
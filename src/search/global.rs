@@ -0,0 +1,470 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::thread;
+
+use crossbeam as cb;
+use crossterm::queue;
+use crossterm::style::{Attribute, Color, Print, SetAttribute};
+
+use anyhow::{self as ah, Context};
+
+use crate::database::database::{SubjectFiles, SubjectName, XinY};
+use crate::find::{print_highlighted, print_vimgrep};
+use crate::language::language::Language;
+use crate::search::engines::{Match, SearchEngine, Span};
+use crate::search::index::Index;
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Field, Origin, Query};
+use crate::search::scope::Scope;
+use crate::search::symbols::symbols;
+
+/// Every match found in a single subject's document.
+#[derive(Debug, Clone)]
+pub struct SubjectHits {
+    pub subject: SubjectName,
+    pub path: PathBuf,
+
+    /// How the document is referred to in the output; the subject, prefixed
+    /// with the language tag when searching across languages, e.g. de-de/go.
+    pub label: String,
+
+    /// The lines of the document, kept so that matches can be printed.
+    pub lines: Vec<(usize, String)>,
+
+    /// The matches, in document order.
+    pub matches: Vec<Match>,
+}
+
+/// How search_all searches every document.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    /// Which lines of the documents are searched.
+    pub scope: Scope,
+
+    pub normalizer: Normalizer,
+
+    /// If set, matches are windows of up to that many lines.
+    pub near: Option<usize>,
+
+    /// Only keep the best match of every section of a document.
+    pub collapse: bool,
+}
+
+/// A document to search, and if an index was used, the only lines of it that
+/// could possibly match.
+type Job<'a> = (
+    Origin,
+    &'a SubjectFiles,
+    &'a Language,
+    Option<BTreeSet<usize>>,
+);
+
+/// The lines of every document worth searching, or None for all of them,
+/// keyed by language tag and subject.
+type Candidates = BTreeMap<(String, SubjectName), Option<BTreeSet<usize>>>;
+
+/// The documents containing every term somewhere, rather than on a single
+/// line, as the terms of a window may each be on a line of their own. Every
+/// line of them has to be searched, since the lines in between the terms are
+/// needed to tell whether the window contains any excluded terms.
+fn candidate_documents(
+    index: &Index,
+    lang: Option<&Language>,
+    terms: &[String],
+    stem: bool,
+) -> Candidates {
+    let mut documents: Option<BTreeSet<(String, SubjectName)>> = None;
+
+    for term in terms {
+        let found: BTreeSet<(String, SubjectName)> = index
+            .candidates(lang, std::slice::from_ref(term), stem)
+            .into_keys()
+            .collect();
+
+        documents = Some(match documents {
+            Some(previous) => previous.intersection(&found).cloned().collect(),
+            None => found,
+        });
+    }
+
+    documents
+        .unwrap_or_default()
+        .into_iter()
+        .map(|document| (document, None))
+        .collect()
+}
+
+/// Searches every subject available in the given language for the query,
+/// spreading the documents across one worker thread per available core. If
+/// the query uses the lang: field, every language is searched instead, and
+/// the field decides which ones match. Subjects without any matches are
+/// omitted, and the rest are returned in alphabetical order. Only the lines
+/// within the scope are searched, and if an up-to-date index is provided that
+/// the engine can make use of, only the documents and lines it points to.
+pub fn search_all<SE: SearchEngine + Send>(
+    engine: &SE,
+    xiny: &XinY,
+    lang: &Language,
+    query: &Query,
+    options: SearchOptions,
+    index: Option<&Index>,
+) -> ah::Result<Vec<SubjectHits>> {
+    let SearchOptions {
+        scope,
+        normalizer,
+        near,
+        collapse,
+    } = options;
+
+    let any_language = query.uses_field(Field::Lang);
+    let lang = (!any_language).then_some(lang);
+
+    let required = engine.required_terms(query);
+
+    let candidates: Option<Candidates> = index.zip(required).map(|(index, terms)| match near {
+        Some(_) => candidate_documents(index, lang, &terms, normalizer.stem),
+        None => index
+            .candidates(lang, &terms, normalizer.stem)
+            .into_iter()
+            .map(|(document, lines)| (document, Some(lines)))
+            .collect(),
+    });
+
+    let documents: Vec<Job<'_>> = xiny
+        .subjects
+        .iter()
+        .flat_map(|(name, files)| {
+            files
+                .files
+                .iter()
+                .filter(|(l, _)| lang.is_none_or(|lang| *l == lang))
+                .map(move |(l, _)| {
+                    let origin = Origin {
+                        subject: name.clone(),
+                        lang: l.to_tag(),
+                    };
+
+                    (origin, files, l)
+                })
+        })
+        .filter_map(|(origin, files, l)| match &candidates {
+            Some(candidates) => {
+                let key = (origin.lang.clone(), origin.subject.clone());
+                let wanted = candidates.get(&key)?.clone();
+                Some((origin, files, l, wanted))
+            }
+            None => Some((origin, files, l, None)),
+        })
+        .collect();
+
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(documents.len().max(1));
+
+    let (job_send, job_recv) = cb::channel::unbounded::<Job<'_>>();
+    let (hit_send, hit_recv) = cb::channel::unbounded::<ah::Result<SubjectHits>>();
+
+    for document in documents {
+        job_send
+            .send(document)
+            .map_err(|_| ah::anyhow!("search_all job channel closed"))?;
+    }
+
+    // Closing the job channel lets the workers stop once it has been drained.
+    drop(job_send);
+
+    cb::scope(|threads| {
+        for _ in 0..workers {
+            let job_recv = job_recv.clone();
+            let hit_send = hit_send.clone();
+
+            // Each worker gets its own engine, as engines may cache state.
+            let mut engine = engine.clone();
+
+            threads.spawn(move |_| {
+                for (origin, files, l, wanted) in job_recv.iter() {
+                    let hits = files
+                        .document(l)
+                        .with_context(|| {
+                            format!("search_all reading {}/{}", origin.lang, origin.subject)
+                        })
+                        .and_then(|document| {
+                            let searchable: Vec<_> = scope
+                                .select(&document.lines)
+                                .into_iter()
+                                .filter(|(line_num, _, _)| {
+                                    wanted.as_ref().is_none_or(|w| w.contains(line_num))
+                                })
+                                .collect();
+
+                            let mut matches = match near {
+                                Some(near) => {
+                                    engine.search_near(&searchable, query, &origin, near)?
+                                }
+                                None => engine.search(&searchable, query, &origin)?,
+                            };
+
+                            document.outline.annotate(&mut matches);
+
+                            if collapse {
+                                matches = document.outline.collapse(matches);
+                            }
+
+                            matches.sort_by_key(|m| m.line_num);
+
+                            let label = match any_language {
+                                true => format!("{}/{}", origin.lang, origin.subject),
+                                false => origin.subject.clone(),
+                            };
+
+                            Ok(SubjectHits {
+                                subject: origin.subject,
+                                path: document.path.clone(),
+                                label,
+                                lines: document.plain_lines(),
+                                matches,
+                            })
+                        });
+
+                    if hit_send.send(hits).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    })
+    .map_err(|_| ah::anyhow!("search_all worker thread panicked"))?;
+
+    drop(hit_send);
+
+    let mut results = hit_recv
+        .iter()
+        .filter(|hits| hits.as_ref().map_or(true, |h| !h.matches.is_empty()))
+        .collect::<ah::Result<Vec<SubjectHits>>>()?;
+
+    results.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(results)
+}
+
+/// Finds every occurrence of a symbol in the code blocks of every subject
+/// available in the given language, or only in the given subject, matching
+/// identifiers exactly rather than as text; see symbols::symbols. Every line
+/// it occurs on is a match, and with collapse, only the first one of every
+/// section. If an up-to-date index is provided, only the documents it points
+/// to are read.
+pub fn search_symbol(
+    xiny: &XinY,
+    lang: &Language,
+    subject: Option<&str>,
+    symbol: &str,
+    collapse: bool,
+    index: Option<&Index>,
+) -> ah::Result<Vec<SubjectHits>> {
+    let candidates = index.map(|index| index.symbol_lines(Some(lang), symbol));
+    let mut results = Vec::new();
+
+    for (name, files) in &xiny.subjects {
+        if subject.is_some_and(|subject| subject != name) {
+            continue;
+        }
+
+        let Some(path) = files.files.get(lang) else {
+            continue;
+        };
+
+        let key = (lang.to_tag(), name.clone());
+        if candidates.as_ref().is_some_and(|c| !c.contains_key(&key)) {
+            continue;
+        }
+
+        let document = files
+            .document(lang)
+            .with_context(|| format!("search_symbol reading {}", path.display()))?;
+
+        let mut matches: Vec<Match> = Vec::new();
+
+        for found in symbols(&document.lines)
+            .into_iter()
+            .filter(|s| s.name == symbol)
+        {
+            let mut span = Span::from_bytes(&document.lines[found.line_num].1, found.bytes, symbol);
+            span.line_num = found.line_num;
+
+            match matches.last_mut() {
+                Some(last) if last.line_num == found.line_num => last.spans.push(span),
+                _ => matches.push(Match {
+                    line_num: found.line_num,
+                    end_line: found.line_num,
+                    spans: vec![span],
+                    ..Default::default()
+                }),
+            }
+        }
+
+        if matches.is_empty() {
+            continue;
+        }
+
+        document.outline.annotate(&mut matches);
+
+        if collapse {
+            matches = document.outline.collapse(matches);
+        }
+
+        results.push(SubjectHits {
+            subject: name.clone(),
+            path: path.clone(),
+            label: name.clone(),
+            lines: document.plain_lines(),
+            matches,
+        });
+    }
+
+    results.sort_by(|a, b| a.label.cmp(&b.label));
+    Ok(results)
+}
+
+/// Prints every hit as `subject:line: text`, grouped under a header per
+/// subject stating how many hits it had. Hits spanning several lines print
+/// each of them, the lines without matched spans as `subject-line- text`,
+/// and are set apart from the other hits by `--`, the way grep does.
+pub fn print_hits(results: &[SubjectHits]) -> ah::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let color = io::stdout().is_terminal().then_some(Color::Green);
+
+    for (index, hits) in results.iter().enumerate() {
+        if index > 0 {
+            writeln!(stdout)?;
+        }
+
+        let count = hits.matches.len();
+        let header = format!(
+            "{} ({} hit{})",
+            hits.label,
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+
+        match color {
+            Some(_) => queue!(
+                stdout,
+                SetAttribute(Attribute::Bold),
+                Print(header),
+                SetAttribute(Attribute::Reset),
+                Print('\n')
+            )?,
+            None => writeln!(stdout, "{}", header)?,
+        }
+
+        for (index, matched) in hits.matches.iter().enumerate() {
+            let multiline = |m: &Match| m.end_line > m.line_num;
+
+            if index > 0 && (multiline(matched) || multiline(&hits.matches[index - 1])) {
+                writeln!(stdout, "--")?;
+            }
+
+            for line_num in matched.line_num..=matched.end_line {
+                let Some((line_num, line)) = hits.lines.get(line_num) else {
+                    continue;
+                };
+
+                let spans = matched.spans_on(*line_num);
+
+                let separator = match spans.is_empty() && *line_num != matched.line_num {
+                    true => '-',
+                    false => ':',
+                };
+
+                let prefix = format!("{}{}{}{} ", hits.label, separator, line_num + 1, separator);
+
+                match color {
+                    Some(color) if !spans.is_empty() => {
+                        print_highlighted(&mut stdout, &prefix, line, &spans, color)?
+                    }
+                    _ => writeln!(stdout, "{}{}", prefix, line)?,
+                }
+            }
+        }
+    }
+
+    stdout.flush().context("print_hits flushing stdout")?;
+    Ok(())
+}
+
+/// Prints every hit of every subject in the `path:line:col:text` format.
+pub fn print_hits_vimgrep(results: &[SubjectHits]) -> ah::Result<()> {
+    for hits in results {
+        print_vimgrep(&hits.path, &hits.lines, &hits.matches)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::search::engines::terms::TermSearch;
+
+    #[test]
+    fn test_search_all_with_index() {
+        let root = std::env::temp_dir().join(format!("xiny-search-test-{}", std::process::id()));
+        fs::create_dir_all(root.join("de")).unwrap();
+        fs::write(
+            root.join("go.md"),
+            "# Defer\ndefer fmt.Println()\nfunc f() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("rust.md"),
+            "# Traits\nimpl Trait for T {}\nfn deferred()\n",
+        )
+        .unwrap();
+        fs::write(root.join("de").join("go.md"), "# Aufschieben\ndefer x()\n").unwrap();
+
+        let xiny = XinY::new(&root).unwrap();
+        let index = Index::build(&xiny, "abc123").unwrap();
+        let english = Language::from_tag("en-us").unwrap();
+        let engine = TermSearch::default();
+
+        for terms in [
+            "efer",
+            "def",
+            "fmt.Println",
+            "rintln()",
+            "f",
+            "efer lang:de-de",
+        ] {
+            let query = Query::parse(terms).unwrap();
+
+            let search = |index: Option<&Index>| -> Vec<(String, Vec<usize>)> {
+                search_all(
+                    &engine,
+                    &xiny,
+                    &english,
+                    &query,
+                    SearchOptions::default(),
+                    index,
+                )
+                .unwrap()
+                .into_iter()
+                .map(|hits| {
+                    (
+                        hits.label,
+                        hits.matches.iter().map(|m| m.line_num).collect(),
+                    )
+                })
+                .collect()
+            };
+
+            let unindexed = search(None);
+            assert!(!unindexed.is_empty(), "no hits for {}", terms);
+            assert_eq!(search(Some(&index)), unindexed, "hits for {}", terms);
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::{self as ah, Context};
+
+use crate::database::database::{SubjectName, XinY};
+use crate::language::language::Language;
+use crate::search::normalize::Normalizer;
+use crate::search::stem::{words, Stemmer};
+use crate::search::symbols::symbols;
+
+/// Bumped whenever the on-disk format, or the way tokens are normalized,
+/// changes; an index with a different version is treated as stale.
+const INDEX_VERSION: u32 = 4;
+
+/// Where a token occurs: an index into Index::documents, and a line number.
+pub type Posting = (u32, u32);
+
+/// How many characters the n-grams that tokens are looked up by have. Terms
+/// shorter than this have to be compared against every token instead.
+pub const NGRAM: usize = 3;
+
+/// Every n-gram of characters of the word, in order.
+fn ngrams(word: &str) -> Vec<Vec<char>> {
+    let chars: Vec<char> = word.chars().collect();
+    chars.windows(NGRAM).map(<[char]>::to_vec).collect()
+}
+
+/// The tokens of the index, and which of them contain every n-gram, to find
+/// the tokens a term occurs within without comparing it against all of them.
+#[derive(Debug, Clone, Default)]
+struct Ngrams {
+    tokens: Vec<String>,
+
+    /// Indices into tokens, in ascending order.
+    containing: HashMap<Vec<char>, Vec<u32>>,
+}
+
+impl Ngrams {
+    fn new(postings: &BTreeMap<String, Vec<Posting>>) -> Self {
+        let mut index = Ngrams {
+            tokens: postings.keys().cloned().collect(),
+            ..Default::default()
+        };
+
+        for (id, token) in index.tokens.iter().enumerate() {
+            for ngram in ngrams(token) {
+                let containing = index.containing.entry(ngram).or_default();
+
+                // N-grams repeated within a token only need one entry.
+                if containing.last() != Some(&(id as u32)) {
+                    containing.push(id as u32);
+                }
+            }
+        }
+
+        index
+    }
+}
+
+/// Splits a line into normalized tokens; runs of alphanumeric characters (and
+/// underscores), normalized as loosely as any search could, so that the index
+/// finds every line that may match. Everything else separates tokens.
+pub fn tokenize(line: &str) -> Vec<String> {
+    let normalized = Normalizer::LOOSEST.normalize(line, true).text;
+    words(&normalized)
+        .map(|(_, word)| word.to_string())
+        .collect()
+}
+
+/// An inverted index mapping every token in every document of the database
+/// to the lines it occurs on. It is built for a specific commit of the
+/// repository, and is only valid for as long as that commit is checked out.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    /// The commit hash of the repository HEAD the index was built from.
+    pub commit: String,
+
+    /// Every indexed document, as (language tag, subject name).
+    pub documents: Vec<(String, SubjectName)>,
+
+    pub postings: BTreeMap<String, Vec<Posting>>,
+
+    /// The lines of code blocks every symbol occurs on, keyed by the symbol
+    /// exactly as it is written.
+    pub symbols: HashMap<String, Vec<Posting>>,
+
+    /// Built from the postings the first time a term is looked up.
+    ngrams: OnceLock<Ngrams>,
+}
+
+/// Adds a posting, unless the line was the last one added already.
+fn add_posting(postings: &mut Vec<Posting>, posting: Posting) {
+    if postings.last() != Some(&posting) {
+        postings.push(posting);
+    }
+}
+
+impl Index {
+    /// Tokenizes every document of every subject, in every language. Tokens
+    /// are also indexed by their stems, in the language of their document,
+    /// and the symbols of code blocks are indexed separately.
+    pub fn build(xiny: &XinY, commit: &str) -> ah::Result<Self> {
+        let mut index = Index {
+            commit: commit.to_string(),
+            ..Default::default()
+        };
+
+        for (subject, files) in &xiny.subjects {
+            for lang in files.files.keys() {
+                let doc = index.documents.len() as u32;
+                index.documents.push((lang.to_tag(), subject.clone()));
+
+                let stemmer = Stemmer::for_tag(&lang.to_tag());
+
+                let document = files
+                    .document(lang)
+                    .with_context(|| format!("Index::build reading {}", subject))?;
+
+                for (line_num, line, _) in &document.lines {
+                    let tokens = tokenize(line).into_iter().flat_map(|token| {
+                        let stem = stemmer
+                            .map(|stemmer| stemmer.stem(&token))
+                            .filter(|stem| *stem != token);
+
+                        std::iter::once(token).chain(stem)
+                    });
+
+                    for token in tokens {
+                        // Tokens repeated on the same line only need one posting.
+                        let postings = index.postings.entry(token).or_default();
+                        add_posting(postings, (doc, *line_num as u32));
+                    }
+                }
+
+                for symbol in symbols(&document.lines) {
+                    let postings = index.symbols.entry(symbol.name).or_default();
+                    add_posting(postings, (doc, symbol.line_num as u32));
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Writes the index in a line based text format:
+    ///
+    /// ```text
+    /// xiny-index <version>
+    /// <commit>
+    /// D <language tag> <subject>
+    /// T <token> <doc>:<line>,<line>;<doc>:<line>
+    /// S <symbol> <doc>:<line>,<line>;<doc>:<line>
+    /// ```
+    pub fn save(&self, path: &Path) -> ah::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Index::save creating parent directory")?;
+        }
+
+        // Written to a temporary file first, so that an interrupted save never
+        // leaves a truncated index behind.
+        let temporary = path.with_extension("tmp");
+        let file = File::create(&temporary).context("Index::save creating index file")?;
+        let mut writer = BufWriter::new(file);
+
+        writeln!(writer, "xiny-index {}", INDEX_VERSION)?;
+        writeln!(writer, "{}", self.commit)?;
+
+        for (lang, subject) in &self.documents {
+            writeln!(writer, "D {} {}", lang, subject)?;
+        }
+
+        for (token, postings) in &self.postings {
+            writeln!(writer, "T {} {}", token, encode_postings(postings))?;
+        }
+
+        for (symbol, postings) in &self.symbols {
+            writeln!(writer, "S {} {}", symbol, encode_postings(postings))?;
+        }
+
+        writer.flush().context("Index::save flushing index file")?;
+        drop(writer);
+
+        fs::rename(&temporary, path).context("Index::save replacing index file")?;
+        Ok(())
+    }
+
+    /// Reads an index written by Index::save. Fails if the file is missing,
+    /// malformed, or was written by a different version of the format.
+    pub fn load(path: &Path) -> ah::Result<Self> {
+        let file = File::open(path).context("Index::load opening index file")?;
+        let mut lines = BufReader::new(file).lines();
+
+        let mut next_line = || -> ah::Result<String> {
+            lines
+                .next()
+                .context("Index::load unexpected end of file")?
+                .context("Index::load reading line")
+        };
+
+        let header = next_line()?;
+
+        if header != format!("xiny-index {}", INDEX_VERSION) {
+            ah::bail!("Index::load unsupported index header '{}'", header);
+        }
+
+        let mut index = Index {
+            commit: next_line()?,
+            ..Default::default()
+        };
+
+        while let Ok(line) = next_line() {
+            let mut parts = line.splitn(3, ' ');
+
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("D"), Some(lang), Some(subject)) => {
+                    index
+                        .documents
+                        .push((lang.to_string(), subject.to_string()));
+                }
+
+                (Some("T"), Some(token), Some(encoded)) => {
+                    index
+                        .postings
+                        .insert(token.to_string(), decode_postings(encoded)?);
+                }
+
+                (Some("S"), Some(symbol), Some(encoded)) => {
+                    index
+                        .symbols
+                        .insert(symbol.to_string(), decode_postings(encoded)?);
+                }
+
+                _ => ah::bail!("Index::load malformed line '{}'", line),
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Every token the normalized term occurs within. Only the tokens sharing
+    /// the term's rarest n-gram are compared against it, unless it's too short
+    /// to have any.
+    fn tokens_containing(&self, term: &str) -> Vec<&str> {
+        let ngrams = ngrams(term);
+
+        if ngrams.is_empty() {
+            return self
+                .postings
+                .keys()
+                .filter(|token| token.contains(term))
+                .map(String::as_str)
+                .collect();
+        }
+
+        let index = self.ngrams.get_or_init(|| Ngrams::new(&self.postings));
+
+        let rarest = ngrams
+            .iter()
+            .map(|ngram| index.containing.get(ngram).map_or(&[][..], Vec::as_slice))
+            .min_by_key(|ids| ids.len())
+            .unwrap_or_default();
+
+        rarest
+            .iter()
+            .map(|&id| index.tokens[id as usize].as_str())
+            .filter(|token| token.contains(term))
+            .collect()
+    }
+
+    /// Finds the lines of every document in the given language (or in any
+    /// language if None) that could contain all of the terms, where a term may
+    /// occur anywhere within a token (not just as a whole token). When
+    /// stemming, the stem of a term, in the language of a document, may occur
+    /// as a token instead. Returns the candidate line numbers of every such
+    /// document, keyed by language tag and subject.
+    pub fn candidates(
+        &self,
+        lang: Option<&Language>,
+        terms: &[String],
+        stem: bool,
+    ) -> BTreeMap<(String, SubjectName), BTreeSet<usize>> {
+        let tag = lang.map(Language::to_tag);
+
+        let stemmers: Vec<Option<Stemmer>> = self
+            .documents
+            .iter()
+            .map(|(tag, _)| Stemmer::for_tag(tag).filter(|_| stem))
+            .collect();
+
+        let mut candidates: Option<BTreeSet<Posting>> = None;
+
+        for term in terms {
+            let term = Normalizer::LOOSEST.normalize(term, true).text;
+
+            // The stem of the term in the language of every document, if any.
+            let stems: &Vec<Option<String>> = &stemmers
+                .iter()
+                .map(|stemmer| stemmer.map(|stemmer| stemmer.stem(&term)))
+                .collect();
+
+            let mut postings: BTreeSet<Posting> = self
+                .tokens_containing(&term)
+                .into_iter()
+                .flat_map(|token| self.postings[token].iter().copied())
+                .collect();
+
+            // Stems are looked up as whole tokens, but only count for the
+            // documents whose language stems the term that way.
+            for stem in stems.iter().flatten().collect::<BTreeSet<_>>() {
+                let stemmed = self.postings.get(stem).into_iter().flatten();
+
+                postings.extend(
+                    stemmed.filter(|(doc, _)| stems[*doc as usize].as_ref() == Some(stem)),
+                );
+            }
+
+            let postings: BTreeSet<Posting> = postings
+                .into_iter()
+                .filter(|(doc, _)| {
+                    tag.as_ref()
+                        .is_none_or(|tag| self.documents[*doc as usize].0 == *tag)
+                })
+                .collect();
+
+            candidates = Some(match candidates {
+                Some(previous) => previous.intersection(&postings).copied().collect(),
+                None => postings,
+            });
+        }
+
+        let mut by_document: BTreeMap<(String, SubjectName), BTreeSet<usize>> = BTreeMap::new();
+
+        for (doc, line) in candidates.unwrap_or_default() {
+            by_document
+                .entry(self.documents[doc as usize].clone())
+                .or_default()
+                .insert(line as usize);
+        }
+
+        by_document
+    }
+
+    /// Finds the lines of every document in the given language (or in any
+    /// language if None) that the symbol occurs on, exactly as written.
+    /// Returns the line numbers of every such document, keyed by language tag
+    /// and subject.
+    pub fn symbol_lines(
+        &self,
+        lang: Option<&Language>,
+        symbol: &str,
+    ) -> BTreeMap<(String, SubjectName), BTreeSet<usize>> {
+        let tag = lang.map(Language::to_tag);
+        let mut by_document: BTreeMap<(String, SubjectName), BTreeSet<usize>> = BTreeMap::new();
+
+        for (doc, line) in self.symbols.get(symbol).into_iter().flatten() {
+            let document = &self.documents[*doc as usize];
+
+            if tag.as_ref().is_none_or(|tag| document.0 == *tag) {
+                by_document
+                    .entry(document.clone())
+                    .or_default()
+                    .insert(*line as usize);
+            }
+        }
+
+        by_document
+    }
+}
+
+/// Encodes postings as <doc>:<line>,<line>;<doc>:<line>
+fn encode_postings(postings: &[Posting]) -> String {
+    let mut by_doc: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+
+    for (doc, line) in postings {
+        by_doc.entry(*doc).or_default().push(line.to_string());
+    }
+
+    let encoded: Vec<String> = by_doc
+        .into_iter()
+        .map(|(doc, lines)| format!("{}:{}", doc, lines.join(",")))
+        .collect();
+
+    encoded.join(";")
+}
+
+/// Decodes postings written by encode_postings.
+fn decode_postings(encoded: &str) -> ah::Result<Vec<Posting>> {
+    let mut postings = Vec::new();
+
+    for group in encoded.split(';') {
+        let (doc, lines) = group
+            .split_once(':')
+            .with_context(|| format!("Index::load malformed postings '{}'", group))?;
+
+        let doc: u32 = doc.parse().context("Index::load parsing document")?;
+
+        for line in lines.split(',') {
+            postings.push((doc, line.parse().context("Index::load parsing line")?));
+        }
+    }
+
+    Ok(postings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("fmt.Println(\"Grüße\") // snake_case");
+        assert_eq!(tokens, vec!["fmt", "println", "grusse", "snake_case"]);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut index = Index {
+            commit: "abc123".into(),
+            documents: vec![("en-us".into(), "go".into()), ("de-de".into(), "go".into())],
+            ..Default::default()
+        };
+
+        index
+            .postings
+            .insert("defer".into(), vec![(0, 3), (0, 7), (1, 2)]);
+        index.postings.insert("func".into(), vec![(0, 3)]);
+        index
+            .symbols
+            .insert("fmt.Println".into(), vec![(0, 5), (1, 4)]);
+
+        let path = std::env::temp_dir().join(format!("xiny-index-test-{}", std::process::id()));
+        index.save(&path).unwrap();
+        let loaded = Index::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.commit, index.commit);
+        assert_eq!(loaded.documents, index.documents);
+        assert_eq!(loaded.postings, index.postings);
+        assert_eq!(loaded.symbols, index.symbols);
+
+        let english = Language::from_tag("en-us").unwrap();
+        let found = loaded.candidates(Some(&english), &["efer".into(), "func".into()], false);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found.get(&("en-us".into(), "go".into())),
+            Some(&BTreeSet::from([3]))
+        );
+
+        let found = loaded.candidates(None, &["defer".into()], false);
+        assert_eq!(found.len(), 2);
+        assert_eq!(loaded.candidates(None, &["efer".into()], false).len(), 2);
+        assert_eq!(loaded.candidates(None, &["ef".into()], false).len(), 2);
+
+        // Deferring only stems to defer in English.
+        let found = loaded.candidates(None, &["deferring".into()], true);
+        assert_eq!(found.len(), 1);
+
+        let found = loaded.symbol_lines(Some(&english), "fmt.Println");
+        assert_eq!(
+            found.get(&("en-us".into(), "go".into())),
+            Some(&BTreeSet::from([5]))
+        );
+        assert!(loaded.symbol_lines(None, "Println").is_empty());
+    }
+}
@@ -1 +1,10 @@
+pub mod answer;
 pub mod engines;
+pub mod global;
+pub mod index;
+pub mod normalize;
+pub mod outline;
+pub mod query;
+pub mod scope;
+pub mod stem;
+pub mod symbols;
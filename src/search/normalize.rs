@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+/// Whether the case of a term matters when matching it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Case {
+    /// Case insensitive, unless the term contains an uppercase character.
+    Smart,
+
+    Sensitive,
+
+    #[default]
+    Insensitive,
+}
+
+/// Decides how lines and terms are normalized before they're matched, so
+/// that text which reads the same matches the same, no matter how it was
+/// encoded. Canonically equivalent text (NFC/NFD) always matches. When case
+/// insensitive, text is also fully case folded (e.g. ß matches ss), and
+/// compatibility equivalents (NFKC, e.g. ﬁ and fi) match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalizer {
+    pub case: Case,
+
+    /// Strips accents and other diacritics, so that e.g. cafe matches café.
+    pub fold_diacritics: bool,
+
+    /// Also matches words by their stems, in the language of the document,
+    /// so that e.g. iterating matches iterates. Only engines that match
+    /// whole words apply it.
+    pub stem: bool,
+}
+
+/// Text that has been normalized, along with how to find the original text
+/// that any part of it was derived from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Normalized {
+    pub text: String,
+
+    /// The byte offsets at which every segment starts, in the normalized and
+    /// the original text respectively. None if they're always the same.
+    segments: Option<Vec<(usize, usize)>>,
+
+    original_len: usize,
+}
+
+impl Normalized {
+    /// Maps a byte range of the normalized text to the range of the original
+    /// text it was derived from, widened to whole characters if it only
+    /// covers part of what a character was normalized into.
+    pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let Some(segments) = &self.segments else {
+            return range;
+        };
+
+        let first = segments
+            .partition_point(|(text, _)| *text <= range.start)
+            .saturating_sub(1);
+
+        let last = segments
+            .partition_point(|(text, _)| *text < range.end)
+            .saturating_sub(1)
+            .max(first);
+
+        let end = segments
+            .get(last + 1)
+            .map_or(self.original_len, |(_, original)| *original);
+
+        segments[first].1..end
+    }
+}
+
+/// Whether the character belongs to one of the blocks of combining marks
+/// that are used as diacritics, e.g. the acute accent of é once decomposed.
+/// Marks that are integral parts of a script, e.g. Devanagari vowel signs,
+/// are deliberately left alone.
+fn is_diacritic(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+impl Normalizer {
+    /// The loosest normalization, under which anything that matches under
+    /// any other normalization also matches. Used by the Index, so that its
+    /// candidates never miss a line.
+    pub const LOOSEST: Normalizer = Normalizer {
+        case: Case::Insensitive,
+        fold_diacritics: true,
+        stem: false,
+    };
+
+    /// Whether the term should be matched case insensitively. Smart case is
+    /// decided per term, like fzf does.
+    pub fn folds_case(&self, term: &str) -> bool {
+        match self.case {
+            Case::Insensitive => true,
+            Case::Sensitive => false,
+            Case::Smart => !term.chars().any(char::is_uppercase),
+        }
+    }
+
+    /// Normalizes a single segment, i.e. a character and the combining marks
+    /// following it, appending the result to out.
+    fn normalize_segment(&self, segment: &str, fold_case: bool, out: &mut String) {
+        let decomposed: Vec<char> = match fold_case {
+            true => {
+                // Full case folding turns İ into i followed by a combining dot
+                // above, which is redundant, as the i already has a dot.
+                let folded = caseless::default_case_fold_str(segment).replace("i\u{307}", "i");
+                folded.nfkd().collect()
+            }
+            false => segment.nfd().collect(),
+        };
+
+        let kept = decomposed
+            .into_iter()
+            .filter(|c| !(self.fold_diacritics && is_diacritic(*c)));
+
+        out.extend(kept.nfc());
+    }
+
+    /// Normalizes the text, case folding it if fold_case is set.
+    pub fn normalize(&self, text: &str, fold_case: bool) -> Normalized {
+        // ASCII text is already normalized, and only ever needs lowercasing,
+        // which leaves every byte where it is.
+        if text.is_ascii() {
+            return Normalized {
+                text: match fold_case {
+                    true => text.to_ascii_lowercase(),
+                    false => text.to_string(),
+                },
+                segments: None,
+                original_len: text.len(),
+            };
+        }
+
+        let mut normalized = String::with_capacity(text.len());
+        let mut segments: Vec<(usize, usize)> = Vec::new();
+
+        // Every segment starts at a character that doesn't combine with the
+        // ones before it, and is normalized on its own.
+        let mut starts: Vec<usize> = text
+            .char_indices()
+            .filter(|(i, c)| *i == 0 || canonical_combining_class(*c) == 0)
+            .map(|(i, _)| i)
+            .collect();
+
+        starts.push(text.len());
+
+        for bounds in starts.windows(2) {
+            segments.push((normalized.len(), bounds[0]));
+            self.normalize_segment(&text[bounds[0]..bounds[1]], fold_case, &mut normalized);
+        }
+
+        Normalized {
+            text: normalized,
+            segments: Some(segments),
+            original_len: text.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let insensitive = Normalizer::default();
+        let loosest = Normalizer::LOOSEST;
+
+        assert_eq!(
+            insensitive.normalize("ПРИВЕТ Straße", true).text,
+            "привет strasse"
+        );
+        assert_eq!(
+            insensitive.normalize("ﬁle İstanbul", true).text,
+            "file istanbul"
+        );
+        assert_eq!(insensitive.normalize("Café", false).text, "Café");
+        assert_eq!(
+            loosest.normalize("Cafe\u{301} naïve", true).text,
+            "cafe naive"
+        );
+
+        // Decomposed and precomposed text normalize to the same thing.
+        assert_eq!(
+            insensitive.normalize("e\u{301}", true),
+            Normalized {
+                text: "é".into(),
+                segments: Some(vec![(0, 0)]),
+                original_len: 3
+            }
+        );
+
+        let normalized = insensitive.normalize("Größe", true);
+        assert_eq!(normalized.text, "grösse");
+
+        // "ss" came from ß, which spans two bytes of the original.
+        let ss = normalized.text.find("ss").unwrap();
+        assert_eq!(normalized.original_range(ss..ss + 2), 4..6);
+        assert_eq!(normalized.original_range(ss..ss + 1), 4..6);
+        assert_eq!(normalized.original_range(0..normalized.text.len()), 0..7);
+
+        let smart = Normalizer {
+            case: Case::Smart,
+            ..Default::default()
+        };
+
+        assert!(smart.folds_case("straße"));
+        assert!(!smart.folds_case("Straße"));
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use crate::search::engines::Match;
+use crate::search::scope::BlockKind;
+
+/// A heading of a document, and how deeply it's nested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// The zero-based line number of the heading; the text line of a setext
+    /// heading, rather than its underline.
+    pub line_num: usize,
+
+    /// 1 for `#` and `===` underlined headings, up to 6 for `######`.
+    pub level: usize,
+
+    pub text: String,
+}
+
+/// The heading structure of a document, which tells where in it a line is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Outline {
+    /// The name of the document from its front matter, e.g. Python, which
+    /// every breadcrumb starts with. Set by the Document the outline is of.
+    pub title: Option<String>,
+
+    /// Every heading, in document order.
+    pub headings: Vec<Heading>,
+}
+
+/// The text of a heading line, without the ATX #'s around it.
+pub fn heading_text(line: &str) -> String {
+    line.trim()
+        .trim_start_matches('#')
+        .trim_end_matches('#')
+        .trim()
+        .to_string()
+}
+
+/// The anchor a heading's text gets, GitHub style, e.g. goroutines for
+/// Goroutines, or ifelse-statements for If/else statements.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_alphanumeric() || c == '_' || c == '-' => Some(c),
+            c if c.is_whitespace() => Some('-'),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Outline {
+    pub fn new(lines: &[(usize, String, BlockKind)]) -> Self {
+        let mut outline = Outline::default();
+
+        for (index, (line_num, line, kind)) in lines.iter().enumerate() {
+            if *kind == BlockKind::Heading {
+                let underline = lines
+                    .get(index + 1)
+                    .filter(|(_, _, kind)| *kind == BlockKind::Underline)
+                    .map(|(_, underline, _)| underline.trim());
+
+                let level = match underline {
+                    Some(underline) if underline.starts_with('=') => 1,
+                    Some(_) => 2,
+                    None => line.trim_start().chars().take_while(|&c| c == '#').count(),
+                };
+
+                outline.headings.push(Heading {
+                    line_num: *line_num,
+                    level,
+                    text: heading_text(line),
+                });
+            }
+        }
+
+        outline
+    }
+
+    /// The heading of the section the line is in, i.e. the last heading at or
+    /// before it, regardless of its level. None before the first heading.
+    pub fn section(&self, line_num: usize) -> Option<&Heading> {
+        let index = self.headings.partition_point(|h| h.line_num <= line_num);
+        index.checked_sub(1).map(|index| &self.headings[index])
+    }
+
+    /// The chain of headings enclosing the line, from the title of the
+    /// document down to the heading of its section, e.g. Rust, Traits and
+    /// Generic bounds.
+    pub fn breadcrumb(&self, line_num: usize) -> Vec<String> {
+        let mut chain: Vec<&Heading> = Vec::new();
+
+        for heading in self.headings.iter().take_while(|h| h.line_num <= line_num) {
+            while chain.last().is_some_and(|last| last.level >= heading.level) {
+                chain.pop();
+            }
+
+            chain.push(heading);
+        }
+
+        self.title
+            .iter()
+            .cloned()
+            .chain(chain.into_iter().map(|h| h.text.clone()))
+            .collect()
+    }
+
+    /// Attaches the breadcrumb of every match, by the line it starts on.
+    pub fn annotate(&self, matches: &mut [Match]) {
+        for matched in matches {
+            matched.breadcrumb = self.breadcrumb(matched.line_num);
+        }
+    }
+
+    /// Keeps only the first match of every section, which is the best one if
+    /// the matches are ordered by relevance.
+    pub fn collapse(&self, matches: Vec<Match>) -> Vec<Match> {
+        let mut seen: HashSet<Option<usize>> = HashSet::new();
+
+        matches
+            .into_iter()
+            .filter(|m| seen.insert(self.section(m.line_num).map(|h| h.line_num)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::document::Document;
+    use crate::search::scope::tag_lines;
+    use std::path::Path;
+
+    #[test]
+    fn test_outline() {
+        let document =
+            "---\nname: Rust\n---\nIntro\n## Traits\ntext\n### Generic bounds\nfn f<T>()\n\
+            Lifetimes\n---------\n'a\n# Appendix\nend";
+
+        let lines: Vec<(usize, String)> = document.lines().map(String::from).enumerate().collect();
+        let outline = Document::parse(Path::new("rust.md"), &lines).outline;
+
+        let levels: Vec<(usize, usize)> = outline
+            .headings
+            .iter()
+            .map(|h| (h.line_num, h.level))
+            .collect();
+        assert_eq!(levels, vec![(4, 2), (6, 3), (8, 2), (11, 1)]);
+
+        assert_eq!(outline.breadcrumb(3), vec!["Rust"]);
+        assert_eq!(
+            outline.breadcrumb(7),
+            vec!["Rust", "Traits", "Generic bounds"]
+        );
+        assert_eq!(outline.breadcrumb(10), vec!["Rust", "Lifetimes"]);
+        assert_eq!(outline.breadcrumb(12), vec!["Rust", "Appendix"]);
+
+        let matches: Vec<Match> = [7, 3, 6, 5, 10]
+            .iter()
+            .map(|&line_num| Match {
+                line_num,
+                end_line: line_num,
+                ..Default::default()
+            })
+            .collect();
+
+        let collapsed: Vec<usize> = outline
+            .collapse(matches)
+            .iter()
+            .map(|m| m.line_num)
+            .collect();
+        assert_eq!(collapsed, vec![7, 3, 5, 10]);
+
+        let document = "# A\n## B\ntext\n### C\nD\n---\nmore";
+        let lines: Vec<(usize, String)> = document.lines().map(String::from).enumerate().collect();
+        let outline = Outline::new(&tag_lines(&lines));
+
+        let headings: Vec<(usize, usize, &str)> = outline
+            .headings
+            .iter()
+            .map(|h| (h.line_num, h.level, h.text.as_str()))
+            .collect();
+        assert_eq!(
+            headings,
+            vec![(0, 1, "A"), (1, 2, "B"), (3, 3, "C"), (4, 2, "D")]
+        );
+        assert_eq!(outline.breadcrumb(2), vec!["A", "B"]);
+        assert_eq!(outline.breadcrumb(6), vec!["A", "D"]);
+
+        assert_eq!(slug("Generic bounds"), "generic-bounds");
+        assert_eq!(slug(" If/else: Flow "), "ifelse-flow");
+    }
+}
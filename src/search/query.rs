@@ -0,0 +1,414 @@
+use anyhow as ah;
+
+use crate::search::engines::{SearchEngine, Span};
+use crate::search::index::tokenize;
+use crate::search::scope::BlockKind;
+
+/// A prefix restricting what a term is matched against, e.g. `code:defer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// The term must match a line inside of a code block.
+    Code,
+
+    /// The term must match a heading line.
+    Heading,
+
+    /// The document must belong to the subject, e.g. `subject:go`.
+    Subject,
+
+    /// The document must be in the language, either the full tag, or just
+    /// the language part of it, e.g. `lang:de` or `lang:de-de`.
+    Lang,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "code" => Some(Field::Code),
+            "heading" => Some(Field::Heading),
+            "subject" => Some(Field::Subject),
+            "lang" => Some(Field::Lang),
+            _ => None,
+        }
+    }
+}
+
+/// The document a query is evaluated against, which the subject: and lang:
+/// fields are matched with.
+#[derive(Debug, Clone, Default)]
+pub struct Origin {
+    pub subject: String,
+
+    /// The language tag of the document, e.g. en-us.
+    pub lang: String,
+}
+
+/// A parsed search query. How a single term matches a line is up to the
+/// search engine; the query only decides how the terms are combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// A bare word, or a quoted phrase, which may contain whitespace.
+    Term(String),
+
+    Field(Field, String),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+/// A token of the query string, before the structure of the query is known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Or,
+    Operand(Query),
+}
+
+/// Splits off the value of a term, which is either quoted, in which case it
+/// ends at the closing quote, or bare, in which case it ends at whitespace.
+/// Returns the value, and how many bytes of the input it spanned.
+fn take_value(input: &str, column: usize) -> ah::Result<(String, usize)> {
+    if let Some(quoted) = input.strip_prefix('"') {
+        let Some(end) = quoted.find('"') else {
+            ah::bail!("Unterminated quote at column {}", column);
+        };
+
+        if quoted[..end].is_empty() {
+            ah::bail!("Empty phrase at column {}", column);
+        }
+
+        return Ok((quoted[..end].to_string(), end + 2));
+    }
+
+    let end = input.find(char::is_whitespace).unwrap_or(input.len());
+    Ok((input[..end].to_string(), end))
+}
+
+fn tokenize_query(input: &str) -> ah::Result<Vec<Token>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut rest = input;
+
+    loop {
+        rest = rest.trim_start();
+
+        if rest.is_empty() {
+            break;
+        }
+
+        // Columns in error messages start from 1, counted in characters.
+        let column = input[..input.len() - rest.len()].chars().count() + 1;
+
+        // A | is only an operator on its own, so that it can still be used
+        // within a term, e.g. as alternation in a regular expression.
+        if rest
+            .strip_prefix('|')
+            .is_some_and(|r| r.is_empty() || r.starts_with(char::is_whitespace))
+        {
+            tokens.push(Token::Or);
+            rest = &rest[1..];
+            continue;
+        }
+
+        let negated = rest.starts_with('-');
+
+        if negated {
+            rest = &rest[1..];
+
+            if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+                ah::bail!("Expected a term to exclude after '-' at column {}", column);
+            }
+        }
+
+        // Only known field names are treated as a prefix, so that terms such
+        // as std::vec or http://... are searched for as they are.
+        let field = rest
+            .split_once(':')
+            .filter(|(name, _)| !name.contains(char::is_whitespace))
+            .and_then(|(name, value)| Some((Field::from_name(name)?, name.len(), value)));
+
+        let operand = match field {
+            Some((field, length, value)) => {
+                if value.is_empty() || value.starts_with(char::is_whitespace) {
+                    ah::bail!(
+                        "Expected a value after '{}' at column {}",
+                        &rest[..=length],
+                        column
+                    );
+                }
+
+                let (value, taken) = take_value(value, column)?;
+                rest = &rest[length + 1 + taken..];
+                Query::Field(field, value)
+            }
+
+            None => {
+                let (value, taken) = take_value(rest, column)?;
+                rest = &rest[taken..];
+                Query::Term(value)
+            }
+        };
+
+        tokens.push(Token::Operand(match negated {
+            true => Query::Not(Box::new(operand)),
+            false => operand,
+        }));
+    }
+
+    Ok(tokens)
+}
+
+impl Query {
+    /// Parses a query, where whitespace separated terms must all match, terms
+    /// separated by a standalone `|` are alternatives, `-term` excludes lines
+    /// matching the term, and "quoted phrases" are matched as a single term.
+    /// Terms may be prefixed with a field, e.g. `code:defer` or `subject:go`.
+    /// The `|` binds tighter than whitespace, so `a b | c` is `a (b or c)`.
+    pub fn parse(input: &str) -> ah::Result<Self> {
+        let mut groups: Vec<Vec<Query>> = Vec::new();
+        let mut pending_or = false;
+
+        for token in tokenize_query(input)? {
+            match token {
+                Token::Or if !pending_or && !groups.is_empty() => pending_or = true,
+                Token::Or => ah::bail!("Expected a term before '|'"),
+
+                Token::Operand(operand) => match groups.last_mut() {
+                    Some(group) if pending_or => {
+                        group.push(operand);
+                        pending_or = false;
+                    }
+                    _ => groups.push(vec![operand]),
+                },
+            }
+        }
+
+        if pending_or {
+            ah::bail!("Expected a term after '|'");
+        }
+
+        if groups.is_empty() {
+            ah::bail!("Empty query");
+        }
+
+        let mut conjunction: Vec<Query> = groups
+            .into_iter()
+            .map(|mut group| match group.len() {
+                1 => group.remove(0),
+                _ => Query::Or(group),
+            })
+            .collect();
+
+        let query = match conjunction.len() {
+            1 => conjunction.remove(0),
+            _ => Query::And(conjunction),
+        };
+
+        if !query.is_positive() {
+            ah::bail!("The query needs at least one term that isn't excluded");
+        }
+
+        Ok(query)
+    }
+
+    /// Whether the query can only match lines that some term matched, rather
+    /// than every line that merely isn't excluded.
+    fn is_positive(&self) -> bool {
+        match self {
+            Query::Term(_) => true,
+            Query::Field(field, _) => matches!(field, Field::Code | Field::Heading),
+            Query::Not(_) => false,
+            Query::And(queries) => queries.iter().any(Query::is_positive),
+            Query::Or(queries) => queries.iter().all(Query::is_positive),
+        }
+    }
+
+    /// Every term that the engine has to match against lines.
+    pub fn terms(&self) -> Vec<&str> {
+        match self {
+            Query::Term(term) => vec![term.as_str()],
+            Query::Field(Field::Code | Field::Heading, term) => vec![term.as_str()],
+            Query::Field(..) => Vec::new(),
+            Query::Not(query) => query.terms(),
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().flat_map(Query::terms).collect()
+            }
+        }
+    }
+
+    /// Whether the field is used anywhere in the query.
+    pub fn uses_field(&self, field: Field) -> bool {
+        match self {
+            Query::Term(_) => false,
+            Query::Field(f, _) => *f == field,
+            Query::Not(query) => query.uses_field(field),
+            Query::And(queries) | Query::Or(queries) => queries.iter().any(|q| q.uses_field(field)),
+        }
+    }
+
+    /// The tokens every matching line must contain, if each term has to occur
+    /// within the line as a substring. Alternatives and exclusions can't be
+    /// relied on, so only terms that are required outright contribute.
+    pub fn required_tokens(&self) -> Vec<String> {
+        match self {
+            Query::Term(term) | Query::Field(Field::Code | Field::Heading, term) => tokenize(term),
+            Query::And(queries) => queries.iter().flat_map(Query::required_tokens).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Evaluates the query against a single line, using the engine to match
+    /// the terms. Returns the combined score of the matched terms, and their
+    /// spans, or None if the line doesn't satisfy the query.
+    pub fn evaluate<SE: SearchEngine>(
+        &self,
+        engine: &SE,
+        line: &str,
+        kind: BlockKind,
+        origin: &Origin,
+    ) -> Option<(i64, Vec<Span>)> {
+        self.evaluate_with(origin, &|term, required| match required {
+            Some(required) if required != kind => None,
+            _ => engine.match_term(line, term),
+        })
+    }
+
+    /// Evaluates the query, leaving how a term matches up to matches, which
+    /// is given the term, and the kind of block it has to be matched in, if
+    /// it's restricted to one by a field.
+    pub fn evaluate_with<F>(&self, origin: &Origin, matches: &F) -> Option<(i64, Vec<Span>)>
+    where
+        F: Fn(&str, Option<BlockKind>) -> Option<(i64, Vec<Span>)>,
+    {
+        match self {
+            Query::Term(term) => matches(term, None),
+            Query::Field(Field::Code, term) => matches(term, Some(BlockKind::Code)),
+            Query::Field(Field::Heading, term) => matches(term, Some(BlockKind::Heading)),
+
+            Query::Field(Field::Subject, subject) => origin
+                .subject
+                .eq_ignore_ascii_case(subject)
+                .then(|| (0, Vec::new())),
+
+            Query::Field(Field::Lang, lang) => {
+                let tag = origin.lang.to_ascii_lowercase();
+                let lang = lang.to_ascii_lowercase();
+
+                (tag == lang || tag.starts_with(&format!("{}-", lang))).then(|| (0, Vec::new()))
+            }
+
+            Query::Not(query) => match query.evaluate_with(origin, matches) {
+                Some(_) => None,
+                None => Some((0, Vec::new())),
+            },
+
+            Query::And(queries) => {
+                let mut score = 0;
+                let mut spans = Vec::new();
+
+                for query in queries {
+                    let (s, mut found) = query.evaluate_with(origin, matches)?;
+                    score += s;
+                    spans.append(&mut found);
+                }
+
+                Some((score, spans))
+            }
+
+            // Every matching alternative is highlighted, but only the best
+            // one counts towards the score.
+            Query::Or(queries) => {
+                let mut best: Option<i64> = None;
+                let mut spans = Vec::new();
+
+                for query in queries {
+                    if let Some((s, mut found)) = query.evaluate_with(origin, matches) {
+                        best = Some(best.map_or(s, |b| b.max(s)));
+                        spans.append(&mut found);
+                    }
+                }
+
+                best.map(|score| (score, spans))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(s: &str) -> Query {
+        Query::Term(s.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Query::parse("defer").unwrap(), term("defer"));
+
+        assert_eq!(
+            Query::parse("a b | \"c d\" -e").unwrap(),
+            Query::And(vec![
+                term("a"),
+                Query::Or(vec![term("b"), term("c d")]),
+                Query::Not(Box::new(term("e"))),
+            ])
+        );
+
+        assert_eq!(
+            Query::parse("code:\"x y\" -subject:go std::vec a|b").unwrap(),
+            Query::And(vec![
+                Query::Field(Field::Code, "x y".into()),
+                Query::Not(Box::new(Query::Field(Field::Subject, "go".into()))),
+                term("std::vec"),
+                term("a|b"),
+            ])
+        );
+
+        for invalid in [
+            "", "| a", "a |", "a | | b", "a -", "\"a b", "a \"\"", "code:", "-a",
+        ] {
+            assert!(Query::parse(invalid).is_err(), "{}", invalid);
+        }
+
+        let error = Query::parse("a \"b c").unwrap_err().to_string();
+        assert_eq!(error, "Unterminated quote at column 3");
+    }
+    #[test]
+    fn test_evaluate() {
+        use crate::search::engines::terms::TermSearch;
+
+        let origin = Origin {
+            subject: "go".into(),
+            lang: "de-de".into(),
+        };
+
+        let evaluate = |query: &str, line: &str, kind: BlockKind| {
+            Query::parse(query)
+                .unwrap()
+                .evaluate(&TermSearch::default(), line, kind, &origin)
+                .map(|(score, _)| score)
+        };
+
+        assert_eq!(
+            evaluate("defer func", "defer func() {}", BlockKind::Code),
+            Some(2)
+        );
+        assert_eq!(
+            evaluate("defer | panic", "defer defer", BlockKind::Code),
+            Some(2)
+        );
+        assert_eq!(
+            evaluate("defer -func", "defer func() {}", BlockKind::Code),
+            None
+        );
+        assert_eq!(evaluate("code:defer", "defer", BlockKind::Prose), None);
+        assert_eq!(
+            evaluate("heading:defer", "## Defer", BlockKind::Heading),
+            Some(1)
+        );
+        assert_eq!(
+            evaluate("defer subject:GO lang:de", "defer", BlockKind::Prose),
+            Some(1)
+        );
+        assert_eq!(evaluate("defer lang:en", "defer", BlockKind::Prose), None);
+    }
+}
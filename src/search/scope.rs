@@ -0,0 +1,278 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+
+/// What kind of Markdown block a line of a document belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+    /// The YAML front matter at the very top of the document, including the
+    /// `---` lines delimiting it.
+    FrontMatter,
+
+    /// The opening or closing line of a fenced code block, e.g. ```python
+    Fence,
+
+    /// A line inside of a fenced code block.
+    Code,
+
+    /// An ATX (`## Heading`) heading, or the text line of a setext heading.
+    Heading,
+
+    /// The `===` or `---` line underlining a setext heading.
+    Underline,
+
+    /// Any other non-blank line; paragraphs, lists, quotes, tables, etc.
+    Prose,
+
+    Blank,
+}
+
+/// Restricts which lines of a document are searched, by their block kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Scope {
+    /// Every line of the document.
+    #[default]
+    All,
+
+    /// Only lines inside of fenced code blocks.
+    Code,
+
+    /// Only lines outside of code blocks that aren't headings.
+    Prose,
+
+    /// Only heading lines.
+    Headings,
+}
+
+impl Scope {
+    pub fn includes(&self, kind: BlockKind) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Code => kind == BlockKind::Code,
+            Scope::Prose => kind == BlockKind::Prose,
+            Scope::Headings => kind == BlockKind::Heading,
+        }
+    }
+
+    /// Selects the lines within the scope, in the form the engines expect.
+    pub fn select(&self, lines: &[(usize, String, BlockKind)]) -> Vec<(usize, String, BlockKind)> {
+        lines
+            .iter()
+            .filter(|(_, _, kind)| self.includes(*kind))
+            .cloned()
+            .collect()
+    }
+}
+
+/// If the line opens or closes a code fence, returns the fence character and
+/// how many times it was repeated, e.g. ('`', 3) for ```rust
+fn fence(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    let c = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let length = trimmed.chars().take_while(|&x| x == c).count();
+
+    (length >= 3).then_some((c, length))
+}
+
+/// Whether the line is an ATX heading, i.e. 1-6 #'s followed by a space.
+fn is_atx_heading(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+
+    (1..=6).contains(&hashes)
+        && trimmed[hashes..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+}
+
+/// Whether the line could underline a setext heading, i.e. only ='s or -'s.
+fn is_setext_underline(line: &str) -> bool {
+    let trimmed = line.trim();
+    let first = trimmed.chars().next();
+
+    matches!(first, Some('=') | Some('-')) && trimmed.chars().all(|c| Some(c) == first)
+}
+
+/// Determines the block kind of every line of a Markdown document.
+pub fn tag_lines(lines: &[(usize, String)]) -> Vec<(usize, String, BlockKind)> {
+    let mut kinds: Vec<BlockKind> = Vec::with_capacity(lines.len());
+
+    // The fence that opened the current code block, if inside of one.
+    let mut open_fence: Option<(char, usize)> = None;
+
+    let has_front_matter = lines.first().is_some_and(|(_, l)| l.trim_end() == "---");
+    let mut in_front_matter = has_front_matter;
+
+    for (index, (_, line)) in lines.iter().enumerate() {
+        let kind = if in_front_matter {
+            if index > 0 && matches!(line.trim_end(), "---" | "...") {
+                in_front_matter = false;
+            }
+
+            BlockKind::FrontMatter
+        } else if let Some((c, length)) = open_fence {
+            // A closing fence uses the same character, at least as many times,
+            // and has nothing following it.
+            match fence(line) {
+                Some((close, close_length))
+                    if close == c
+                        && close_length >= length
+                        && line.trim().len() == close_length =>
+                {
+                    open_fence = None;
+                    BlockKind::Fence
+                }
+                _ => BlockKind::Code,
+            }
+        } else if let Some(opened) = fence(line) {
+            open_fence = Some(opened);
+            BlockKind::Fence
+        } else if line.trim().is_empty() {
+            BlockKind::Blank
+        } else if is_atx_heading(line) {
+            BlockKind::Heading
+        } else if is_setext_underline(line) && kinds.last() == Some(&BlockKind::Prose) {
+            // The underline turns the preceding line of prose into a heading.
+            if let Some(previous) = kinds.last_mut() {
+                *previous = BlockKind::Heading;
+            }
+
+            BlockKind::Underline
+        } else {
+            BlockKind::Prose
+        };
+
+        kinds.push(kind);
+    }
+
+    lines
+        .iter()
+        .zip(kinds)
+        .map(|((line_num, line), kind)| (*line_num, line.clone(), kind))
+        .collect()
+}
+
+/// The indices of the lines making up the Markdown block that the line at
+/// index is in: a whole fenced code block along with its fences, a paragraph,
+/// the front matter, or a heading along with the block it introduces. Blank
+/// lines are a block of their own.
+pub fn block_range(lines: &[(usize, String, BlockKind)], index: usize) -> Range<usize> {
+    let kind_at = |i: usize| lines[i].2;
+
+    // Extends the range over the adjacent lines satisfying the predicate.
+    let extend = |range: Range<usize>, part: &dyn Fn(BlockKind) -> bool| {
+        let start = (0..range.start)
+            .rev()
+            .take_while(|&i| part(kind_at(i)))
+            .last()
+            .unwrap_or(range.start);
+
+        let end = (range.end..lines.len())
+            .take_while(|&i| part(kind_at(i)))
+            .last()
+            .map_or(range.end, |i| i + 1);
+
+        start..end
+    };
+
+    match kind_at(index) {
+        BlockKind::Code | BlockKind::Fence => {
+            // Fences alternate between opening and closing code blocks, so
+            // the number of fences before a fence tells which of the two it is.
+            let fences_before = (0..index)
+                .filter(|&i| kind_at(i) == BlockKind::Fence)
+                .count();
+            let is_fence = kind_at(index) == BlockKind::Fence;
+
+            let start = match is_fence && fences_before % 2 == 0 {
+                true => index,
+                false => (0..index)
+                    .rev()
+                    .find(|&i| kind_at(i) == BlockKind::Fence)
+                    .unwrap_or(0),
+            };
+
+            // Unclosed code blocks run to the end of the document.
+            let end = match is_fence && fences_before % 2 == 1 {
+                true => index + 1,
+                false => (index + 1..lines.len())
+                    .find(|&i| kind_at(i) == BlockKind::Fence)
+                    .map_or(lines.len(), |i| i + 1),
+            };
+
+            start..end
+        }
+
+        BlockKind::Heading | BlockKind::Underline => {
+            // A setext heading spans its text line and its underline.
+            let heading = match kind_at(index) {
+                BlockKind::Underline => index.saturating_sub(1)..index + 1,
+                _ if index + 1 < lines.len() && kind_at(index + 1) == BlockKind::Underline => {
+                    index..index + 2
+                }
+                _ => index..index + 1,
+            };
+
+            // The block introduced by the heading, past any blank lines.
+            let next = (heading.end..lines.len()).find(|&i| kind_at(i) != BlockKind::Blank);
+
+            match next {
+                Some(next) if kind_at(next) != BlockKind::Heading => {
+                    heading.start..block_range(lines, next).end
+                }
+                _ => heading,
+            }
+        }
+
+        BlockKind::Prose => extend(index..index + 1, &|k| k == BlockKind::Prose),
+        BlockKind::FrontMatter => extend(index..index + 1, &|k| k == BlockKind::FrontMatter),
+        BlockKind::Blank => index..index + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_lines() {
+        let document =
+            "---\nname: Go\n---\n\n# Go\nSome prose.\n\n```go\n# not a heading\n```\nTitle\n=====";
+
+        let lines: Vec<(usize, String)> = document.lines().map(String::from).enumerate().collect();
+
+        let kinds: Vec<BlockKind> = tag_lines(&lines).into_iter().map(|(_, _, k)| k).collect();
+
+        use BlockKind::*;
+
+        assert_eq!(
+            kinds,
+            vec![
+                FrontMatter,
+                FrontMatter,
+                FrontMatter,
+                Blank,
+                Heading,
+                Prose,
+                Blank,
+                Fence,
+                Code,
+                Fence,
+                Heading,
+                Underline
+            ]
+        );
+
+        let tagged = tag_lines(&lines);
+        let blocks: Vec<Range<usize>> = [1, 4, 5, 6, 7, 8, 9, 10]
+            .iter()
+            .map(|&index| block_range(&tagged, index))
+            .collect();
+
+        assert_eq!(
+            blocks,
+            vec![0..3, 4..6, 5..6, 6..7, 7..10, 7..10, 7..10, 10..12]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use std::ops::Range;
+
+use rust_stemmers::Algorithm;
+
+/// Reduces the words of a language to their stems, so that the inflections of
+/// a word, e.g. iterate, iterates and iterating, all match each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stemmer {
+    algorithm: Algorithm,
+}
+
+impl Stemmer {
+    /// The stemmer for the language of a tag, e.g. en-us or de-de, keyed on
+    /// its language part alone. None if there's no stemmer for the language.
+    pub fn for_tag(tag: &str) -> Option<Self> {
+        let language = tag.split('-').next()?.to_ascii_lowercase();
+
+        let algorithm = match language.as_str() {
+            "ar" => Algorithm::Arabic,
+            "da" => Algorithm::Danish,
+            "de" => Algorithm::German,
+            "el" => Algorithm::Greek,
+            "en" => Algorithm::English,
+            "es" => Algorithm::Spanish,
+            "fi" => Algorithm::Finnish,
+            "fr" => Algorithm::French,
+            "hu" => Algorithm::Hungarian,
+            "it" => Algorithm::Italian,
+            "nb" | "nn" | "no" => Algorithm::Norwegian,
+            "nl" => Algorithm::Dutch,
+            "pt" => Algorithm::Portuguese,
+            "ro" => Algorithm::Romanian,
+            "ru" => Algorithm::Russian,
+            "sv" => Algorithm::Swedish,
+            "ta" => Algorithm::Tamil,
+            "tr" => Algorithm::Turkish,
+            _ => return None,
+        };
+
+        Some(Self { algorithm })
+    }
+
+    /// The stem of a single word, which is expected to be case folded already.
+    pub fn stem(&self, word: &str) -> String {
+        rust_stemmers::Stemmer::create(self.algorithm)
+            .stem(word)
+            .into_owned()
+    }
+}
+
+/// Every word of the text along with its byte range, where words are runs of
+/// alphanumeric characters and underscores, the same as the tokens of an Index.
+pub fn words(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(move |word| {
+            // Every word is a subslice of the text, which gives its offset.
+            let start = word.as_ptr() as usize - text.as_ptr() as usize;
+            (start..start + word.len(), word)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stem() {
+        let english = Stemmer::for_tag("en-us").unwrap();
+        let stems: Vec<String> = ["iterate", "iterates", "iterating"]
+            .iter()
+            .map(|word| english.stem(word))
+            .collect();
+        assert!(stems.iter().all(|stem| *stem == stems[0]));
+
+        let german = Stemmer::for_tag("de-de").unwrap();
+        assert_eq!(german.stem("listen"), german.stem("liste"));
+
+        assert_eq!(Stemmer::for_tag("ja-jp"), None);
+
+        let found: Vec<(Range<usize>, &str)> = words("fmt.Println(x_1)").collect();
+        assert_eq!(
+            found,
+            vec![(0..3, "fmt"), (4..11, "Println"), (12..15, "x_1")]
+        );
+    }
+}
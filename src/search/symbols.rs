@@ -0,0 +1,356 @@
+use std::ops::Range;
+
+use crate::search::scope::BlockKind;
+
+/// How identifiers, comments and strings are written in a programming
+/// language, as far as telling its identifiers apart goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Syntax {
+    /// Characters identifiers may contain besides alphanumerics and
+    /// underscores, e.g. - and ? in Lisps.
+    word_chars: &'static str,
+
+    /// What joins the parts of a qualified name, e.g. :: in std::mem::swap.
+    separators: &'static [&'static str],
+
+    line_comments: &'static [&'static str],
+
+    block_comment: Option<(&'static str, &'static str)>,
+
+    /// The characters string literals are quoted with.
+    quotes: &'static str,
+}
+
+const C_LIKE: Syntax = Syntax {
+    word_chars: "",
+    separators: &[".", "::", "->"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: "\"'",
+};
+
+const SCRIPT: Syntax = Syntax {
+    word_chars: "",
+    separators: &["."],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: "\"'",
+};
+
+const LISP: Syntax = Syntax {
+    word_chars: "-?!*<>=+",
+    separators: &[".", "/"],
+    line_comments: &[";"],
+    block_comment: None,
+    quotes: "\"",
+};
+
+impl Syntax {
+    /// The syntax of the language of a fence's info string, e.g. rust for
+    /// ```rust, falling back to C's for languages it doesn't know about.
+    fn for_language(language: &str) -> Syntax {
+        match language {
+            "rust" | "rs" => Syntax {
+                // ' starts lifetimes as well as characters.
+                quotes: "\"",
+                ..C_LIKE
+            },
+            "go" | "golang" | "javascript" | "js" | "typescript" | "ts" | "kotlin" | "scala"
+            | "swift" | "dart" => Syntax {
+                quotes: "\"'`",
+                ..C_LIKE
+            },
+            "python" | "py" | "python3" | "nim" | "r" | "julia" | "coffeescript" | "yaml"
+            | "toml" | "make" | "makefile" | "cmake" | "awk" => SCRIPT,
+            "bash" | "sh" | "shell" | "zsh" | "fish" | "powershell" | "ps1" => Syntax {
+                separators: &[],
+                ..SCRIPT
+            },
+            "ruby" | "rb" | "crystal" => Syntax {
+                word_chars: "?!",
+                separators: &[".", "::"],
+                ..SCRIPT
+            },
+            "elixir" | "ex" => Syntax {
+                word_chars: "?!",
+                ..SCRIPT
+            },
+            "perl" | "pl" | "raku" | "perl6" => Syntax {
+                separators: &["::", "->", "."],
+                ..SCRIPT
+            },
+            "php" => Syntax {
+                separators: &["->", "::", "\\"],
+                line_comments: &["//", "#"],
+                ..C_LIKE
+            },
+            "clojure" | "clj" | "elisp" | "emacs-lisp" | "lisp" | "common-lisp" | "racket"
+            | "scheme" | "fennel" | "hy" => LISP,
+            "haskell" | "hs" | "elm" | "purescript" | "idris" | "agda" => Syntax {
+                word_chars: "'",
+                separators: &["."],
+                line_comments: &["--"],
+                block_comment: Some(("{-", "-}")),
+                quotes: "\"",
+            },
+            "lua" => Syntax {
+                separators: &[".", ":"],
+                line_comments: &["--"],
+                block_comment: None,
+                ..C_LIKE
+            },
+            "sql" | "plsql" | "tsql" => Syntax {
+                separators: &["."],
+                line_comments: &["--"],
+                ..C_LIKE
+            },
+            "ocaml" | "ml" | "fsharp" | "f#" | "sml" => Syntax {
+                word_chars: "'",
+                separators: &["."],
+                line_comments: &["//"],
+                block_comment: Some(("(*", "*)")),
+                quotes: "\"",
+            },
+            "erlang" | "erl" => Syntax {
+                separators: &[":"],
+                line_comments: &["%"],
+                block_comment: None,
+                quotes: "\"",
+                ..C_LIKE
+            },
+            "matlab" | "octave" | "latex" | "tex" | "prolog" => Syntax {
+                separators: &["."],
+                line_comments: &["%"],
+                block_comment: None,
+                ..C_LIKE
+            },
+            "css" | "scss" | "sass" | "less" => Syntax {
+                word_chars: "-",
+                separators: &[],
+                ..C_LIKE
+            },
+            "html" | "xml" | "svg" => Syntax {
+                word_chars: "-",
+                separators: &[":"],
+                line_comments: &[],
+                block_comment: Some(("<!--", "-->")),
+                quotes: "\"'",
+            },
+            _ => C_LIKE,
+        }
+    }
+
+    fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || self.word_chars.contains(c)
+    }
+}
+
+/// The language of a fence line's info string, lowercased, e.g. python for
+/// ```Python or ~~~ {.python}. Empty if the fence doesn't name one.
+pub fn fence_language(line: &str) -> String {
+    line.trim()
+        .trim_start_matches(['`', '~'])
+        .trim_start_matches([' ', '{', '.'])
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '}')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// A symbol occurring in a code block; either a single identifier, or a
+/// qualified name of several of them, e.g. fmt.Println.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub line_num: usize,
+
+    /// The byte range of the symbol within its line.
+    pub bytes: Range<usize>,
+
+    pub name: String,
+}
+
+/// Every symbol in the code blocks of a document, in document order, found by
+/// the rules of the language each block's fence is tagged with. Comments and
+/// string literals are skipped, and numbers aren't symbols. Besides every
+/// identifier, each run of identifiers joined by the language's separators
+/// is a symbol as well, e.g. fmt.Println yields fmt, Println and fmt.Println.
+pub fn symbols(lines: &[(usize, String, BlockKind)]) -> Vec<Symbol> {
+    let mut found = Vec::new();
+    let mut syntax = C_LIKE;
+
+    // The closing delimiter of the block comment being skipped, if any.
+    let mut in_comment: Option<&'static str> = None;
+
+    for (line_num, line, kind) in lines {
+        match kind {
+            BlockKind::Fence => {
+                syntax = Syntax::for_language(&fence_language(line));
+                in_comment = None;
+            }
+            BlockKind::Code => {
+                scan_line(&syntax, *line_num, line, &mut in_comment, &mut found);
+            }
+            _ => {}
+        }
+    }
+
+    found
+}
+
+/// The symbols of a single line of code, continuing any block comment left
+/// open by the previous line.
+fn scan_line(
+    syntax: &Syntax,
+    line_num: usize,
+    line: &str,
+    in_comment: &mut Option<&'static str>,
+    found: &mut Vec<Symbol>,
+) {
+    // The identifiers of the qualified name being read, and whether the last
+    // thing read was a separator following one of them.
+    let mut path: Vec<Range<usize>> = Vec::new();
+    let mut after_separator = false;
+
+    let flush = |path: &mut Vec<Range<usize>>, found: &mut Vec<Symbol>| {
+        for first in 0..path.len() {
+            for last in first..path.len() {
+                let bytes = path[first].start..path[last].end;
+                found.push(Symbol {
+                    line_num,
+                    name: line[bytes.clone()].to_string(),
+                    bytes,
+                });
+            }
+        }
+
+        path.clear();
+    };
+
+    let mut i = 0;
+
+    while i < line.len() {
+        let rest = &line[i..];
+
+        if let Some(close) = *in_comment {
+            match rest.find(close) {
+                Some(end) => {
+                    i += end + close.len();
+                    *in_comment = None;
+                    continue;
+                }
+                None => break,
+            }
+        }
+
+        if syntax.line_comments.iter().any(|c| rest.starts_with(c)) {
+            break;
+        }
+
+        if let Some((open, close)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            flush(&mut path, found);
+            after_separator = false;
+            *in_comment = Some(close);
+            i += open.len();
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+
+        if syntax.quotes.contains(c) {
+            flush(&mut path, found);
+            after_separator = false;
+
+            // Strings end at the next unescaped quote, or with the line.
+            let mut escaped = false;
+            let end = rest[1..]
+                .char_indices()
+                .find(|&(_, x)| {
+                    let closes = x == c && !escaped;
+                    escaped = x == '\\' && !escaped;
+                    closes
+                })
+                .map_or(line.len(), |(offset, _)| i + 1 + offset + 1);
+
+            i = end;
+            continue;
+        }
+
+        if syntax.is_word_char(c) {
+            let length = rest
+                .find(|x: char| !syntax.is_word_char(x))
+                .unwrap_or(rest.len());
+
+            if c.is_ascii_digit() {
+                flush(&mut path, found);
+            } else {
+                if !after_separator {
+                    flush(&mut path, found);
+                }
+
+                path.push(i..i + length);
+            }
+
+            after_separator = false;
+            i += length;
+            continue;
+        }
+
+        if let Some(separator) = syntax.separators.iter().find(|s| rest.starts_with(*s)) {
+            if !path.is_empty() && !after_separator {
+                after_separator = true;
+                i += separator.len();
+                continue;
+            }
+        }
+
+        flush(&mut path, found);
+        after_separator = false;
+        i += c.len_utf8();
+    }
+
+    flush(&mut path, found);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::scope::tag_lines;
+
+    #[test]
+    fn test_symbols() {
+        let document = "```go\nfmt.Println(\"defer\", x1) // defer\ndefer f()\n```\n\
+            text\n```clojure\n(str/join \"-\" [1 2]) ; join\n```";
+
+        let lines: Vec<(usize, String)> = document.lines().map(String::from).enumerate().collect();
+        let names: Vec<(usize, String)> = symbols(&tag_lines(&lines))
+            .into_iter()
+            .map(|s| (s.line_num, s.name))
+            .collect();
+
+        let expected = [
+            (1, "fmt"),
+            (1, "fmt.Println"),
+            (1, "Println"),
+            (1, "x1"),
+            (2, "defer"),
+            (2, "f"),
+            (6, "str"),
+            (6, "str/join"),
+            (6, "join"),
+        ];
+
+        assert_eq!(
+            names,
+            expected
+                .iter()
+                .map(|(n, s)| (*n, s.to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(fence_language("``` Rust"), "rust");
+        assert_eq!(fence_language("~~~ {.python}"), "python");
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{self as ah, Context};
+use serde_json::json;
+
+use crate::database::document::{CodeBlock, Document, Section};
+use crate::info::Format;
+use crate::search::outline::slug;
+
+/// Prints every heading of the document, indented by its level, along with
+/// its one-based line number, or as a JSON array of them.
+pub fn print_toc(document: &Document, format: Format) -> ah::Result<()> {
+    let mut stdout = io::stdout().lock();
+    let headings = &document.outline.headings;
+
+    if format == Format::Json {
+        let entries: Vec<_> = headings
+            .iter()
+            .map(|h| {
+                json!({
+                    "level": h.level,
+                    "text": h.text,
+                    "anchor": slug(&h.text),
+                    "line": h.line_num + 1,
+                })
+            })
+            .collect();
+
+        writeln!(stdout, "{}", serde_json::to_string_pretty(&entries)?)?;
+        return Ok(());
+    }
+
+    // Documents rarely use a single top level heading, so the shallowest
+    // level present is the one that isn't indented.
+    let top = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let width = document.lines.len().to_string().len();
+
+    for heading in headings {
+        writeln!(
+            stdout,
+            "{:>width$}  {}{}",
+            heading.line_num + 1,
+            "  ".repeat(heading.level - top),
+            heading.text
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The Markdown of a section, from its heading up to the next heading of the
+/// same or a higher level.
+pub fn section_markdown(document: &Document, section: &Section) -> String {
+    document.lines[section.lines.clone()]
+        .iter()
+        .map(|(_, line, _)| line.clone() + "\n")
+        .collect()
+}
+
+/// The code blocks of the document, or only those within the section, in
+/// document order.
+pub fn code_blocks<'a>(document: &'a Document, section: Option<&Section>) -> Vec<&'a CodeBlock> {
+    document
+        .code_blocks()
+        .into_iter()
+        .filter(|block| section.is_none_or(|s| s.lines.contains(&block.lines.start)))
+        .collect()
+}
+
+/// The bodies of the code blocks without their fences, each followed by a
+/// blank line but the last.
+pub fn code_text(document: &Document, blocks: &[&CodeBlock]) -> String {
+    let bodies: Vec<String> = blocks
+        .iter()
+        .map(|block| {
+            document.lines[block.body.clone()]
+                .iter()
+                .map(|(_, line, _)| line.clone() + "\n")
+                .collect()
+        })
+        .collect();
+
+    bodies.join("\n")
+}
+
+/// Writes the code to a file in the directory, which is created if need be,
+/// and returns the path of the file. The file is named after the filename the
+/// front matter gives, e.g. learnpython.py, or after the subject and the
+/// language of the first block otherwise.
+pub fn write_code(
+    dir: &Path,
+    document: &Document,
+    subject: &str,
+    blocks: &[&CodeBlock],
+) -> ah::Result<PathBuf> {
+    // Only the name of the file is used, so that it can't point elsewhere.
+    let filename = document
+        .front_matter
+        .as_ref()
+        .and_then(|fm| fm.get("filename"))
+        .and_then(|filename| Path::new(filename).file_name())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let extension = blocks
+                .first()
+                .map(|block| block.language.as_str())
+                .filter(|language| !language.is_empty())
+                .unwrap_or("txt");
+
+            PathBuf::from(format!("{}.{}", subject, extension))
+        });
+
+    fs::create_dir_all(dir).context("write_code creating output directory")?;
+
+    let path = dir.join(filename);
+    fs::write(&path, code_text(document, blocks)).context("write_code writing code")?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks() {
+        let source =
+            "---\nfilename: ../learngo.go\n---\n```go\na()\n```\n# Defer\n```go\nb()\nc()\n```";
+        let lines: Vec<(usize, String)> = source.lines().map(String::from).enumerate().collect();
+        let document = Document::parse(Path::new("go.md"), &lines);
+
+        let blocks = code_blocks(&document, None);
+        assert_eq!(code_text(&document, &blocks), "a()\n\nb()\nc()\n");
+
+        let section = document.find_section("defer");
+        let scoped = code_blocks(&document, section);
+        assert_eq!(code_text(&document, &scoped), "b()\nc()\n");
+
+        let dir = std::env::temp_dir().join(format!("xiny-code-test-{}", std::process::id()));
+        let path = write_code(&dir, &document, "go", &blocks).unwrap();
+        assert_eq!(path, dir.join("learngo.go"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a()\n\nb()\nc()\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
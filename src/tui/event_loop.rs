@@ -1,22 +1,21 @@
-use std::hash::DefaultHasher;
-use std::hash::Hash;
-use std::hash::Hasher;
 use std::io::stdout;
-use std::path::PathBuf;
-use std::process::abort;
-use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
+use super::history::History;
 use super::input_handler;
 use super::render;
 
-use crate::search::engines::SearchEngine;
+use crate::database::document::Document;
+use crate::find::MatchContext;
+use crate::search::engines::any::AnyEngine;
+use crate::search::engines::{sort_by_relevance, EngineKind, Match, SearchEngine};
+use crate::search::normalize::Normalizer;
+use crate::search::query::{Origin, Query};
+use crate::search::scope::{BlockKind, Scope};
 use crate::utils::Dimensions;
 
-use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, Read};
-use std::sync::{atomic, Arc};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crossbeam as cb;
 
@@ -34,39 +33,84 @@ use anyhow::Context;
 pub enum ViMode {
     Normal,
     Insert,
+
+    /// Searching the history for a query containing the typed pattern.
+    HistorySearch,
 }
 
+/// What the main thread and the search thread tell each other. Every query
+/// is tagged with the generation of the search buffer it was taken from, and
+/// its results with the same one, so that results can be told to be stale.
 #[derive(Debug, Clone)]
 pub enum SearchThreadMessage {
-    Query(String),
-    Results(Vec<(usize, usize)>),
+    /// A query to search for with an engine, from the main thread.
+    Query {
+        generation: u64,
+        query: String,
+        engine: EngineKind,
+    },
+
+    /// The matches of the query of a generation, ordered by relevance, or why
+    /// the query was rejected, from the search thread.
+    Results {
+        generation: u64,
+        results: Result<Vec<Match>, String>,
+    },
+
+    /// Tells the search thread to stop.
     Kill,
 }
 
 #[derive(Debug)]
 pub struct TuiState {
-    /// Kills the event loop when set to true. This will also set st_kill, and
-    /// wait for the search thread to join before properly exiting.
+    /// Kills the event loop when set to true. This will also send Kill to the
+    /// search thread, and wait for it to join before properly exiting.
     pub el_kill: bool,
 
     pub search_query: String,
-    pub search_results: Vec<(usize, usize)>, // Row, Col
+    pub search_results: Vec<Match>,
     pub search_result_index: usize,
     pub search_buffer: String,
     pub search_cursor_index: usize,
 
+    /// Bumped on every change to the search buffer; only the results of the
+    /// current generation are displayed.
+    pub search_generation: u64,
+
+    /// The engine the query is searched with, which Tab cycles through.
+    pub search_engine: EngineKind,
+
+    /// Set when the search engine rejected the last query, e.g. because of
+    /// an invalid regular expression. Displayed in place of the results.
+    pub search_error: Option<String>,
+
     pub st_handle: Option<JoinHandle<()>>,
-    pub st_kill: Arc<atomic::AtomicBool>,
 
-    pub search_buffer_history: Vec<String>,
+    /// The queries submitted for the subject, recalled with Up/Down or k/j.
+    pub search_buffer_history: History,
+
+    /// The history entry the buffer was recalled from, if any.
+    pub history_index: Option<usize>,
+
+    /// The buffer as it was before recalling, restored when going past the
+    /// most recent entry.
+    pub history_draft: String,
+
+    /// What the history is searched for while in ViMode::HistorySearch.
+    pub history_pattern: String,
 
     pub vi_mode: ViMode,
     pub vi_chord: Vec<char>,
 
-    pub document_lines: Vec<(usize, String)>,
+    /// The document being searched, shared with the search thread.
+    pub document: Arc<Document>,
+
+    /// Which lines of the document the search is restricted to.
+    pub search_scope: Scope,
 
     pub preview_offset: isize,
-    pub preview_context: usize,
+    /// Which lines around the selected match the previewer shows.
+    pub preview_context: MatchContext,
     pub preview_dimensions: Dimensions,
 }
 
@@ -74,87 +118,216 @@ impl Default for TuiState {
     fn default() -> Self {
         Self {
             el_kill: false,
-            st_kill: Arc::new(atomic::AtomicBool::new(false)),
             vi_mode: ViMode::Normal,
-            preview_context: 3,
+            preview_context: MatchContext::default(),
             search_query: String::new(),
             search_results: Vec::new(),
             search_result_index: 0,
             search_buffer: String::new(),
             search_cursor_index: 0,
+            search_generation: 0,
+            search_engine: EngineKind::default(),
+            search_error: None,
             st_handle: None,
-            search_buffer_history: Vec::new(),
+            search_buffer_history: History::default(),
+            history_index: None,
+            history_draft: String::new(),
+            history_pattern: String::new(),
             vi_chord: Vec::new(),
-            document_lines: Vec::new(),
+            document: Arc::default(),
+            search_scope: Scope::All,
             preview_offset: 0,
             preview_dimensions: Dimensions { height: 10, width: 80 },
         }
     }
 }
 
-pub fn event_loop<SE: SearchEngine>(subject: PathBuf) -> ah::Result<()> {
-    let mut state = TuiState::default();
+/// How long the search buffer has to stay unchanged before it's searched, so
+/// that typing a word doesn't search every prefix of it along the way.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(75);
+
+/// How many lines the search thread searches before checking whether the
+/// query is stale already.
+const SEARCH_CHUNK_LINES: usize = 256;
+
+/// Searches the lines for the query, a chunk of lines at a time, abandoning
+/// the search as soon as a newer message arrives, as its results would be
+/// stale. Windows of lines (--near) may span chunks, so those are searched in
+/// one go, and only checked afterwards. The engine scans the whole document
+/// once up front, so that engines running an external command don't run it
+/// for every chunk. None if the search was abandoned.
+fn search_until_stale<SE: SearchEngine>(
+    engine: &mut SE,
+    lines: &[(usize, String, BlockKind)],
+    query: &str,
+    origin: &Origin,
+    near: Option<usize>,
+    messages: &cb::channel::Receiver<SearchThreadMessage>,
+) -> Option<ah::Result<Vec<Match>>> {
+    // An empty buffer simply clears the results, rather than being reported
+    // as an invalid query.
+    if query.trim().is_empty() {
+        return Some(Ok(Vec::new()));
+    }
+
+    let query = match Query::parse(query) {
+        Ok(query) => query,
+        Err(e) => return Some(Err(e)),
+    };
+
+    let results = match near {
+        Some(near) => engine.search_near(lines, &query, origin, near),
+        None => {
+            if let Err(e) = engine
+                .prepare(&query, origin)
+                .and_then(|_| engine.scan(lines, &query))
+            {
+                return Some(Err(e));
+            }
+
+            let mut matches: Vec<Match> = Vec::new();
 
-    let file = OpenOptions::new()
-        .read(true)
-        .open(&subject)
-        .context("Failed to open file")?;
+            for chunk in lines.chunks(SEARCH_CHUNK_LINES) {
+                if !messages.is_empty() {
+                    return None;
+                }
 
-    let reader = BufReader::new(file);
-    state.document_lines = reader.lines().filter_map(Result::ok).enumerate().collect();
+                match engine.search(chunk, &query, origin) {
+                    Ok(found) => matches.extend(found),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
 
-    // We'll send queries to the thread using st_query_send, that one's
-    // for us. The search thread will receive queries from st_query_recv,
-    // that one's for the thread. We'll move st_query_recv into the thread.
-    let (st_query_send, st_query_recv) = cb::channel::unbounded::<String>();
+            // Stable, so equally relevant matches remain in document order,
+            // just as if the lines had been searched all at once.
+            sort_by_relevance(&mut matches);
+            Ok(matches)
+        }
+    };
 
-    // The thread will send results to us using st_result_send, that one's
-    // for the thread. We'll receive them using st_result_recv, that one's
-    // for us. We'll move st_result_send into the thread.
-    let (st_result_send, st_result_recv) = cb::channel::unbounded::<Vec<(usize, usize)>>();
+    messages.is_empty().then_some(results)
+}
 
-    // We'll also make a copy of the atomic kill switch for the thread to
-    // know when to stop looping and die.
-    let st_kill = state.st_kill.clone();
+/// How the event loop searches the document, and previews its matches.
+#[derive(Debug, Clone, Default)]
+pub struct EventLoopOptions {
+    /// Which lines of the document are searched.
+    pub scope: Scope,
 
-    // A copy for the search thread.
-    let document_lines = state.document_lines.clone();
+    pub normalizer: Normalizer,
 
-    state.st_handle = Some(thread::spawn(move || {
-        let st_query_recv = st_query_recv;
-        let st_result_send = st_result_send;
-        let st_kill = st_kill;
-        let lines = document_lines;
+    /// If set, matches are windows of up to that many lines.
+    pub near: Option<usize>,
 
-        // The search thread needs a search engine to use.
-        let mut st_search_engine = SE::default();
+    /// Which lines around the selected match the previewer shows.
+    pub context: MatchContext,
 
-        while !st_kill.load(Ordering::SeqCst) {
-            match st_query_recv.try_recv() {
-                Ok(query) => {
-                    let results = st_search_engine.search(&lines, &query);
+    /// The engine searched with until another one is picked.
+    pub engine: AnyEngine,
 
-                    if let Err(e) = st_result_send.send(results) {
-                        // We can't send results back to the main thread.
-                        // We should kill the thread and abort the program.
-                        // Without the ability to send results back, then
-                        // the program is in an unrecoverable state.
+    /// The command of the external engine, for when it's picked later on.
+    pub search_command: String,
+}
+
+pub fn event_loop(
+    document: Arc<Document>,
+    origin: Origin,
+    options: EventLoopOptions,
+) -> ah::Result<()> {
+    let EventLoopOptions {
+        scope,
+        normalizer,
+        near,
+        context,
+        engine,
+        search_command,
+    } = options;
+
+    let mut state = TuiState {
+        document: Arc::clone(&document),
+        search_scope: scope,
+        search_engine: engine.kind(),
+        search_buffer_history: History::load(&History::path_for(&origin)?)
+            .context("Failed to load search history")?,
+        preview_context: context,
+        ..Default::default()
+    };
 
-                        eprintln!("Failed to send results back to main thread: {:?}", e);
-                        abort();
+    // We'll send queries, and eventually Kill, to the search thread using
+    // st_message_send, that one's for us. The thread receives them from
+    // st_message_recv, which we'll move into it.
+    let (st_message_send, st_message_recv) = cb::channel::unbounded::<SearchThreadMessage>();
+
+    // The thread will hand results to us using st_result_send, and we take
+    // them using st_result_recv. The channel has no capacity, so results wait
+    // inside of the thread until we take them, and the thread can drop them
+    // if they go stale in the meantime.
+    let (st_result_send, st_result_recv) = cb::channel::bounded::<SearchThreadMessage>(0);
+
+    // A copy for the search thread, of only the lines it should search.
+    let document_lines = state.search_scope.select(&document.lines);
+
+    state.st_handle = Some(thread::spawn(move || {
+        let st_message_recv = st_message_recv;
+        let st_result_send = st_result_send;
+        let lines = document_lines;
+
+        // The search thread needs a search engine to use, which is swapped
+        // whenever a query asks for a different one.
+        let mut st_search_engine = engine;
+
+        // Results that haven't been taken by the main thread yet.
+        let mut pending: Option<SearchThreadMessage> = None;
+
+        loop {
+            // Block until either the main thread takes the pending results,
+            // or a message arrives, which makes them stale if it's a query.
+            let message = match pending.take() {
+                Some(results) => cb::select! {
+                    send(st_result_send, results) -> sent => match sent {
+                        Ok(()) => continue,
+                        Err(_) => break,
+                    },
+                    recv(st_message_recv) -> message => message,
+                },
+                None => st_message_recv.recv(),
+            };
+
+            match message {
+                Ok(SearchThreadMessage::Query {
+                    generation,
+                    query,
+                    engine,
+                }) => {
+                    if st_search_engine.kind() != engine {
+                        st_search_engine = AnyEngine::of(engine, normalizer, &search_command);
                     }
-                }
 
-                Err(cb::channel::TryRecvError::Empty) => {
-                    // We don't want to overwhelm the CPU, so we'll sleep.
-                    thread::sleep(Duration::from_millis(5));
+                    let results = search_until_stale(
+                        &mut st_search_engine,
+                        &lines,
+                        &query,
+                        &origin,
+                        near,
+                        &st_message_recv,
+                    );
+
+                    pending = results.map(|results| SearchThreadMessage::Results {
+                        generation,
+                        results: results
+                            .map(|mut matches| {
+                                // The headings enclosing every result.
+                                document.outline.annotate(&mut matches);
+                                matches
+                            })
+                            .map_err(|e| format!("{:#}", e)),
+                    });
                 }
 
-                Err(cb::channel::TryRecvError::Disconnected) => {
-                    // If the main thread receiver has disconnected, then
-                    // we should definitely kill the thread.
-                    break;
-                }
+                Ok(SearchThreadMessage::Results { .. }) => {}
+
+                // If the main thread is gone, there's no one to search for.
+                Ok(SearchThreadMessage::Kill) | Err(_) => break,
             }
         }
     }));
@@ -173,32 +346,67 @@ pub fn event_loop<SE: SearchEngine>(subject: PathBuf) -> ah::Result<()> {
     enable_raw_mode()?;
     stdout().execute(Hide)?;
 
-    let mut hasher = DefaultHasher::new();
-    state.search_buffer.hash(&mut hasher);
-    let mut prev_hash = hasher.finish();
+    // The buffer and engine as of the last change, and when the query is to
+    // be sent to the search thread, if it's yet to be.
+    let mut last_buffer = state.search_buffer.clone();
+    let mut last_engine = state.search_engine;
+    let mut send_at: Option<Instant> = None;
 
     while !state.el_kill {
         input_handler::handle_inputs(&mut state)?;
-        // --- Receive Search Results -----------------------------------------
-        // It makes more sense to do this first, as we avoid sleeping through
-        // the time taken to handle input events, and then render the TUI. It
-        // gives the search thread a little more time to do its work.
 
-        if let Ok(results) = st_result_recv.try_recv() {
-            state.search_results = results;
+        // --- Send Search Query ----------------------------------------------
+        // Every change to the buffer starts a new generation, which makes the
+        // results of every previous one stale, but the query is only sent once
+        // the buffer has stopped changing for a moment. Switching engines has
+        // no typing to wait out, and re-runs the query right away.
+        if state.search_buffer != last_buffer {
+            last_buffer.clone_from(&state.search_buffer);
+            state.search_generation += 1;
+            send_at = Some(Instant::now() + SEARCH_DEBOUNCE);
         }
 
-        // --- Send Search Query ----------------------------------------------
-        //  TODO: Implement a debounce mechanism to not spam the sarch thread.
-        //  In other words, only pass the search buffer to the search thread
-        //  after an amount of milliseconds has passed since the last change.
-        state.search_buffer.hash(&mut hasher);
+        if state.search_engine != last_engine {
+            last_engine = state.search_engine;
+            state.search_generation += 1;
+            send_at = Some(Instant::now());
+        }
+
+        if send_at.is_some_and(|at| Instant::now() >= at) {
+            st_message_send
+                .send(SearchThreadMessage::Query {
+                    generation: state.search_generation,
+                    query: state.search_buffer.clone(),
+                    engine: state.search_engine,
+                })
+                .context("Failed to send query to search thread")?;
+
+            send_at = None;
+        }
 
-        let new_hash = hasher.finish();
+        // --- Receive Search Results -----------------------------------------
+        // Results of any generation but the current one are of an outdated
+        // query, and are never displayed.
+        if let Ok(SearchThreadMessage::Results {
+            generation,
+            results,
+        }) = st_result_recv.try_recv()
+        {
+            if generation == state.search_generation {
+                match results {
+                    Ok(results) => {
+                        // Results arrive ordered by relevance; start from the best.
+                        state.search_results = results;
+                        state.search_result_index = 0;
+                        state.search_error = None;
+                    }
 
-        if new_hash != prev_hash {
-            st_query_send.send(state.search_buffer.clone()).unwrap();
-            prev_hash = new_hash;
+                    Err(e) => {
+                        state.search_results.clear();
+                        state.search_error = Some(e);
+                    }
+                }
+            }
         }
 
         // Render the TUI based on the state of the event loop, i.e. state.
@@ -208,9 +416,10 @@ pub fn event_loop<SE: SearchEngine>(subject: PathBuf) -> ah::Result<()> {
     }
 
     // If we're here, then the event loop has been killed.
-    // We should also kill the search thread.
+    // We should also kill the search thread. It may have already died, in
+    // which case there's nothing left to kill.
 
-    state.st_kill.store(true, Ordering::SeqCst);
+    let _ = st_message_send.send(SearchThreadMessage::Kill);
 
     state
         .st_handle
@@ -228,3 +437,48 @@ pub fn event_loop<SE: SearchEngine>(subject: PathBuf) -> ah::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::engines::terms::TermSearch;
+    use crate::search::scope::tag_lines;
+
+    #[test]
+    fn test_search_until_stale() {
+        let lines: Vec<(usize, String)> = (0..1000).map(|n| (n, format!("line {}", n))).collect();
+        let tagged = tag_lines(&lines);
+
+        let (send, recv) = cb::channel::unbounded::<SearchThreadMessage>();
+        let mut engine = TermSearch::default();
+        let origin = Origin::default();
+
+        let results = search_until_stale(&mut engine, &tagged, "99", &origin, None, &recv);
+        let found: Vec<usize> = results
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|m| m.line_num)
+            .collect();
+        let expected: Vec<usize> = (0..1000).filter(|n| n.to_string().contains("99")).collect();
+        assert_eq!(found, expected);
+
+        assert!(
+            search_until_stale(&mut engine, &tagged, "", &origin, None, &recv)
+                .unwrap()
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(
+            search_until_stale(&mut engine, &tagged, "|", &origin, None, &recv)
+                .unwrap()
+                .is_err()
+        );
+
+        // A newer message makes the search stale before it's done.
+        send.send(SearchThreadMessage::Kill).unwrap();
+        assert!(search_until_stale(&mut engine, &tagged, "99", &origin, None, &recv).is_none());
+        assert!(search_until_stale(&mut engine, &tagged, "99", &origin, Some(2), &recv).is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::search::query::Origin;
+
+use anyhow::{self as ah, Context};
+
+/// How many queries are kept per subject; older ones are dropped first.
+pub const MAX_ENTRIES: usize = 200;
+
+/// The queries submitted in the TUI for a single subject, oldest first. Every
+/// query is kept once, at the position it was last submitted at.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    /// Where the history is saved to, if anywhere.
+    path: Option<PathBuf>,
+
+    entries: Vec<String>,
+}
+
+impl History {
+    /// Where the history of a subject lives: next to, rather than inside the
+    /// data directory's repository, so that syncing never touches it.
+    pub fn path_for(origin: &Origin) -> ah::Result<PathBuf> {
+        let path = dirs::data_local_dir()
+            .context("History::path_for finding local data directory via dirs::data_local_dir()")?
+            .join("xiny-history")
+            .join(&origin.lang)
+            .join(&origin.subject);
+
+        Ok(path)
+    }
+
+    /// Reads the history at the path, one query per line. A missing file is
+    /// an empty history, which is saved there once a query is submitted.
+    pub fn load(path: &Path) -> ah::Result<Self> {
+        let mut history = History {
+            path: Some(path.to_path_buf()),
+            entries: Vec::new(),
+        };
+
+        if !path.exists() {
+            return Ok(history);
+        }
+
+        let contents = fs::read_to_string(path).context("History::load reading history file")?;
+
+        for line in contents.lines() {
+            history.push(line);
+        }
+
+        Ok(history)
+    }
+
+    pub fn save(&self) -> ah::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("History::save creating parent directory")?;
+        }
+
+        let contents: String = self.entries.iter().map(|e| e.clone() + "\n").collect();
+        fs::write(path, contents).context("History::save writing history file")
+    }
+
+    /// Adds a query as the most recent one, moving it there if it was
+    /// submitted before. Blank queries aren't worth remembering.
+    pub fn push(&mut self, query: &str) {
+        let query = query.trim();
+
+        if query.is_empty() {
+            return;
+        }
+
+        self.entries.retain(|e| e != query);
+        self.entries.push(query.to_string());
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    /// The index of the closest entry before the one at the index (or of the
+    /// most recent entry, given None) containing the pattern.
+    pub fn older(&self, from: Option<usize>, pattern: &str) -> Option<usize> {
+        let end = from.unwrap_or(self.entries.len()).min(self.entries.len());
+
+        self.entries[..end]
+            .iter()
+            .rposition(|e| e.contains(pattern))
+    }
+
+    /// The index of the closest entry after the one at the index containing
+    /// the pattern. None once there are no newer ones.
+    pub fn newer(&self, from: usize, pattern: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(from + 1)
+            .find(|(_, e)| e.contains(pattern))
+            .map(|(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let path = std::env::temp_dir()
+            .join(format!("xiny-history-test-{}", std::process::id()))
+            .join("go");
+
+        let mut history = History::load(&path).unwrap();
+        assert_eq!(history.older(None, ""), None);
+
+        for query in ["defer", "go func", "  ", "defer", "chan"] {
+            history.push(query);
+        }
+
+        assert_eq!(history.entries, vec!["go func", "defer", "chan"]);
+        assert_eq!(history.older(None, ""), Some(2));
+        assert_eq!(history.older(Some(2), "func"), Some(0));
+        assert_eq!(history.older(Some(0), ""), None);
+        assert_eq!(history.newer(0, "ch"), Some(2));
+        assert_eq!(history.newer(2, ""), None);
+
+        history.save().unwrap();
+        assert_eq!(History::load(&path).unwrap().entries, history.entries);
+
+        for n in 0..MAX_ENTRIES {
+            history.push(&n.to_string());
+        }
+
+        assert_eq!(history.entries.len(), MAX_ENTRIES);
+        assert_eq!(history.get(0), Some("0"));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,5 +1,3 @@
-use std::sync::atomic::Ordering;
-
 use super::event_loop::TuiState;
 
 use crate::tui::event_loop::ViMode;
@@ -35,7 +33,7 @@ fn find_words(string: &str) -> Vec<(usize, usize)> {
 }
 
 /// Calculate the new index of the cursor after the word motion.
-pub fn motion_word(str: &String, idx: usize, backward: bool, endwise: bool) -> usize {
+pub fn motion_word(str: &str, idx: usize, backward: bool, endwise: bool) -> usize {
     let words = find_words(str);
 
     let mut new_idx = idx;
@@ -70,6 +68,63 @@ pub fn motion_word(str: &String, idx: usize, backward: bool, endwise: bool) -> u
     new_idx
 }
 
+/// Replaces the buffer with the history entry at the index, or with the
+/// draft it replaced given None, leaving the cursor at its end.
+fn recall(state: &mut TuiState, index: Option<usize>) {
+    if state.history_index.is_none() {
+        state.history_draft.clone_from(&state.search_buffer);
+    }
+
+    let recalled = match index {
+        Some(index) => state.search_buffer_history.get(index).unwrap_or_default(),
+        None => &state.history_draft,
+    };
+
+    state.search_buffer = recalled.to_string();
+    state.search_cursor_index = state.search_buffer.len();
+    state.history_index = index;
+}
+
+/// Recalls the closest older entry containing the pattern, if there is one.
+fn recall_older(state: &mut TuiState, pattern: &str) {
+    if let Some(index) = state
+        .search_buffer_history
+        .older(state.history_index, pattern)
+    {
+        recall(state, Some(index));
+    }
+}
+
+/// Recalls the closest newer entry containing the pattern, or the draft once
+/// past the most recent one.
+fn recall_newer(state: &mut TuiState, pattern: &str) {
+    if let Some(index) = state.history_index {
+        let newer = state.search_buffer_history.newer(index, pattern);
+        recall(state, newer);
+    }
+}
+
+/// Recalls the most recent entry containing the history search pattern.
+fn search_history(state: &mut TuiState) {
+    let found = state
+        .search_buffer_history
+        .older(None, &state.history_pattern);
+
+    if found.is_some() {
+        recall(state, found);
+    }
+}
+
+/// Remembers the buffer as a submitted query, and saves the history.
+fn submit(state: &mut TuiState) {
+    state.search_buffer_history.push(&state.search_buffer);
+    state.history_index = None;
+
+    if let Err(e) = state.search_buffer_history.save() {
+        state.search_error = Some(format!("Failed to save history: {:#}", e));
+    }
+}
+
 pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
     let Ok(event_available) = cte::poll(Duration::from_millis(50)) else {
         return Ok(());
@@ -95,10 +150,59 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
     let _kstate = kevent.state;
 
     match (&state.vi_mode, kcode, kmods) {
-        (ViMode::Insert, KCode::Esc | KCode::Enter, KMods::NONE) => {
+        (ViMode::Insert, KCode::Esc, KMods::NONE) => {
+            state.vi_mode = ViMode::Normal;
+        }
+
+        (ViMode::Insert | ViMode::Normal, KCode::Enter, KMods::NONE) => {
+            submit(state);
+            state.vi_mode = ViMode::Normal;
+        }
+
+        (_, KCode::Tab, KMods::NONE) => {
+            state.search_engine = state.search_engine.next();
+        }
+
+        (ViMode::Insert, KCode::Up, KMods::NONE) => recall_older(state, ""),
+        (ViMode::Insert, KCode::Down, KMods::NONE) => recall_newer(state, ""),
+
+        // History search: like a shell's reverse-i-search, typing narrows
+        // down to the most recent query containing the pattern, Up and Down
+        // move between the ones that do, Enter keeps the recalled query, and
+        // Esc gives up on it.
+        // --------------------------------------------------------------------
+        (ViMode::HistorySearch, KCode::Enter, KMods::NONE) => {
+            state.vi_mode = ViMode::Normal;
+        }
+
+        (ViMode::HistorySearch, KCode::Esc, KMods::NONE) => {
+            if state.history_index.is_some() {
+                recall(state, None);
+            }
+
             state.vi_mode = ViMode::Normal;
         }
 
+        (ViMode::HistorySearch, KCode::Up, KMods::NONE) => {
+            let pattern = state.history_pattern.clone();
+            recall_older(state, &pattern);
+        }
+
+        (ViMode::HistorySearch, KCode::Down, KMods::NONE) => {
+            let pattern = state.history_pattern.clone();
+            recall_newer(state, &pattern);
+        }
+
+        (ViMode::HistorySearch, KCode::Backspace, KMods::NONE) => {
+            state.history_pattern.pop();
+            search_history(state);
+        }
+
+        (ViMode::HistorySearch, KCode::Char(c), _) => {
+            state.history_pattern.push(c);
+            search_history(state);
+        }
+
         (ViMode::Insert, KCode::Char(c), _) => {
             // Ensure the cursor is within bounds before inserting, as
             // String::insert panics if the index is out of bounds (..lol)
@@ -135,10 +239,8 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
             }
         }
 
-        (ViMode::Normal, KCode::Char('r'), KMods::NONE) => {
-            if state.vi_chord.is_empty() {
-                state.vi_chord.push('r');
-            }
+        (ViMode::Normal, KCode::Char('r'), KMods::NONE) if state.vi_chord.is_empty() => {
+            state.vi_chord.push('r');
         }
 
         (ViMode::Normal, KCode::Char('c'), KMods::NONE) => match state.vi_chord.first() {
@@ -146,6 +248,7 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
                 state.search_buffer.clear();
                 state.vi_chord.clear();
                 state.search_cursor_index = 0;
+                state.vi_mode = ViMode::Insert;
             }
             None => {
                 state.vi_chord.push('c');
@@ -222,19 +325,6 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
             state.vi_chord.push('g');
         }
 
-        (ViMode::Normal, KCode::Char('c'), KMods::NONE) => match state.vi_chord.first() {
-            Some('c') => {
-                state.search_buffer.clear();
-                state.vi_chord.clear();
-                state.search_cursor_index = 0;
-                state.vi_mode = ViMode::Insert;
-            }
-            Some(_) => state.vi_chord.clear(),
-            None => {
-                state.vi_chord.push('c');
-            }
-        },
-
         // If there's a pending chord that did not progress, clear it.
         // --------------------------------------------------------------------
         (ViMode::Normal, _, KMods::NONE) if !state.vi_chord.is_empty() => {
@@ -244,7 +334,7 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
         // --------------------------------------------------------------------
 
         (ViMode::Normal, KCode::Char('n'), KMods::NONE) => {
-            if state.search_result_index + 1 > state.search_results.len() {
+            if state.search_result_index + 1 >= state.search_results.len() {
                 state.search_result_index = 0
             } else {
                 state.search_result_index += 1
@@ -255,37 +345,40 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
             if state.search_result_index > 0 {
                 state.search_result_index -= 1;
             } else {
-                state.search_result_index = state.search_results.len() - 1;
+                state.search_result_index = state.search_results.len().saturating_sub(1);
             }
         }
 
 
         (ViMode::Normal, KCode::Char('q'), KMods::NONE) => {
             state.el_kill = true;
-            state.st_kill.store(true, Ordering::SeqCst);
+        }
+
+        (ViMode::Normal, KCode::Char('k'), KMods::NONE) => recall_older(state, ""),
+        (ViMode::Normal, KCode::Char('j'), KMods::NONE) => recall_newer(state, ""),
+
+        (ViMode::Normal, KCode::Char('/'), KMods::NONE) => {
+            state.history_pattern.clear();
+            state.vi_mode = ViMode::HistorySearch;
         }
 
         (ViMode::Normal, KCode::Char('i'), KMods::NONE) => {
             state.vi_mode = ViMode::Insert;
         }
 
-        (ViMode::Normal, KCode::Char('h'), KMods::NONE) => {
-            if state.search_cursor_index > 0 {
-                state.search_cursor_index -= 1;
-            }
+        (ViMode::Normal, KCode::Char('h'), KMods::NONE) if state.search_cursor_index > 0 => {
+            state.search_cursor_index -= 1;
         }
 
-        (ViMode::Insert, KCode::Backspace, KMods::NONE) => {
-            if state.search_cursor_index > 0 {
-                state.search_buffer.remove(state.search_cursor_index - 1);
-                state.search_cursor_index -= 1;
-            }
+        (ViMode::Insert, KCode::Backspace, KMods::NONE) if state.search_cursor_index > 0 => {
+            state.search_buffer.remove(state.search_cursor_index - 1);
+            state.search_cursor_index -= 1;
         }
 
-        (ViMode::Normal, KCode::Char('x'), KMods::NONE) => {
-            if state.search_cursor_index < state.search_buffer.len() {
-                state.search_buffer.remove(state.search_cursor_index);
-            }
+        (ViMode::Normal, KCode::Char('x'), KMods::NONE)
+            if state.search_cursor_index < state.search_buffer.len() =>
+        {
+            state.search_buffer.remove(state.search_cursor_index);
         }
 
         // this is a sentence
@@ -354,10 +447,10 @@ pub fn handle_inputs(state: &mut TuiState) -> ah::Result<()> {
             }
         }
 
-        (ViMode::Normal, KCode::Char('l'), KMods::NONE) => {
-            if state.search_cursor_index < state.search_buffer.len() {
-                state.search_cursor_index += 1;
-            }
+        (ViMode::Normal, KCode::Char('l'), KMods::NONE)
+            if state.search_cursor_index < state.search_buffer.len() =>
+        {
+            state.search_cursor_index += 1;
         }
 
         _ => (),
@@ -1,6 +1,7 @@
 use event_loop::TuiState;
 
 pub mod event_loop;
+pub mod history;
 pub mod input_handler;
 pub mod render;
 
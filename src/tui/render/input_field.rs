@@ -17,15 +17,11 @@ pub fn render(state: &TuiState, _anchor: &(usize, usize)) -> ah::Result<()> {
     let sep = BHCL.to_string().repeat(36);
     let buf = &state.search_buffer;
 
-    let prefix = format!(
-        "[{}] {}",
-        if matches!(state.vi_mode, ViMode::Insert) {
-            "I"
-        } else {
-            "N"
-        },
-        pref
-    );
+    let prefix = match state.vi_mode {
+        ViMode::Insert => format!("[I] {} {}", state.search_engine.name(), pref),
+        ViMode::Normal => format!("[N] {} {}", state.search_engine.name(), pref),
+        ViMode::HistorySearch => format!("[/] history '{}' {}", state.history_pattern, pref),
+    };
 
     // The search input field should look like this:
 
@@ -58,6 +54,21 @@ pub fn render(state: &TuiState, _anchor: &(usize, usize)) -> ah::Result<()> {
         Print(current_char.unwrap_or(' ')),
         ResetColor,
         Print(post_idx_str),
+    )?;
+
+    // Errors from the search engine (e.g. an invalid regex) are shown at the
+    // end of the input line, so they're right where the query is typed.
+    if let Some(error) = &state.search_error {
+        queue!(
+            std::io::stdout(),
+            SetForegroundColor(Color::Red),
+            Print(format!("  ! {}", error)),
+            ResetColor,
+        )?;
+    }
+
+    queue!(
+        std::io::stdout(),
         MoveToNextLine(1),
         Clear(ClearType::CurrentLine),
         Print(&sep),
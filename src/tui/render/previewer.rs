@@ -3,8 +3,13 @@ use super::*;
 
 // Renders the search input field.
 
+use crate::find::MatchContext;
 use crate::tui::event_loop::TuiState;
 
+/// How many lines of the document the previewer shows, unless given a number
+/// of lines of context before and after the match.
+const PREVIEW_LINES: usize = 7;
+
 use crossterm::{
     cursor::MoveToNextLine,
     queue,
@@ -22,21 +27,30 @@ pub fn render_previewer(state: &TuiState, _anchor: &(usize, usize)) -> ah::Resul
 
     let no_results = state.search_results.is_empty();
 
-    let context = state.preview_context;
     let selected = state.search_result_index;
 
-    let (srow, _scol) = match state.search_results.get(selected) {
-        Some(s) => *s,
-        None => (context, 0),
-    };
+    let selected_match = state.search_results.get(selected);
+
+    let srow = selected_match.map_or(0, |m| m.line_num);
 
     let _offset = &state.preview_offset;
-    let lines_to_show = context * 2 + 1;
+
+    // The previewer keeps the same height from one match to the next, so
+    // smart context only decides which lines of the document fill it. The
+    // entire document doesn't fit, so it's treated the same way.
+    let (first, lines_to_show) = match state.preview_context {
+        MatchContext::Lines { before, after } => (srow.saturating_sub(before), before + after + 1),
+        _ => {
+            let smart = MatchContext::Smart { cap: PREVIEW_LINES };
+            let first = selected_match.map_or(0, |m| smart.range(&state.document.lines, m).start);
+            (first, PREVIEW_LINES)
+        }
+    };
 
     let percentage = if no_results {
         0
     } else {
-        let total = state.document_lines.len() as f64;
+        let total = state.document.lines.len() as f64;
         let current = srow as f64;
         let percent = (current / total) * 100.0;
         percent as usize
@@ -47,10 +61,11 @@ pub fn render_previewer(state: &TuiState, _anchor: &(usize, usize)) -> ah::Resul
         MoveToNextLine(1),
         Clear(ClearType::CurrentLine),
         Print(format!(
-            "[{}/{}] {}%",
+            "[{}/{}] {}% {}",
             state.search_result_index,
             state.search_results.len(),
-            percentage
+            percentage,
+            selected_match.map_or(String::new(), |m| m.breadcrumb.join(" > "))
         )),
         MoveToNextLine(1),
         Print(&separator),
@@ -58,25 +73,40 @@ pub fn render_previewer(state: &TuiState, _anchor: &(usize, usize)) -> ah::Resul
     )?;
 
 
-    for (num, line) in state
-        .document_lines
-        .iter()
-        .skip(if srow == 0 {
-            0
-        } else {
-            srow.saturating_sub(context)
-        })
-        .take(lines_to_show)
-    {
+    for (num, line, _kind) in state.document.lines.iter().skip(first).take(lines_to_show) {
         // let line = format!("{:0width$}", line, width = 6);
 
-        if *num == srow && !no_results {
+        if let Some(m) = selected_match.filter(|m| m.contains_line(*num)) {
             queue!(
                 std::io::stdout(),
+                Clear(ClearType::CurrentLine),
                 SetBackgroundColor(Color::Green),
                 SetForegroundColor(Color::Black),
-                Clear(ClearType::CurrentLine),
-                Print(&line),
+            )?;
+
+            // The matched spans are drawn in yellow on top of the green line.
+            let mut cursor = 0;
+
+            for span in &m.spans_on(*num) {
+                // Spans of different terms may overlap; skip the latecomers.
+                if span.bytes.start < cursor {
+                    continue;
+                }
+
+                queue!(
+                    std::io::stdout(),
+                    Print(&line[cursor..span.bytes.start]),
+                    SetBackgroundColor(Color::Yellow),
+                    Print(&line[span.bytes.clone()]),
+                    SetBackgroundColor(Color::Green),
+                )?;
+
+                cursor = span.bytes.end;
+            }
+
+            queue!(
+                std::io::stdout(),
+                Print(&line[cursor..]),
                 ResetColor,
                 MoveToNextLine(1),
             )?;
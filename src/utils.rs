@@ -1,8 +1,9 @@
 use std::process::{ChildStderr, ChildStdout, Command, Stdio};
+use std::thread;
 use std::{
     fs::OpenOptions,
-    io::{BufRead, BufReader, Read},
-    path::PathBuf,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
 };
 
 use anyhow::{self as ah, Context};
@@ -20,7 +21,7 @@ pub struct Dimensions {
     pub height: usize,
 }
 
-pub fn crc32(data: &Vec<u8>) -> String {
+pub fn crc32(data: &[u8]) -> String {
     let result = data.iter().fold(0, |acc, b| (acc << 8) ^ *b as u32);
     format!("{:08x}", result)
 }
@@ -65,6 +66,17 @@ pub fn count_file_lines(path: &PathBuf) -> ah::Result<usize> {
     Ok(reader.lines().count())
 }
 
+/// Reads every line of a file, paired with its (zero-based) line number.
+pub fn read_lines(path: &Path) -> ah::Result<Vec<(usize, String)>> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .context("read_lines opening file")?;
+
+    let reader = BufReader::new(file);
+    Ok(reader.lines().map_while(Result::ok).enumerate().collect())
+}
+
 /// Gets a single key press from the terminal.
 pub fn get_input() -> ah::Result<KeyCode> {
     if !is_raw_mode_enabled()? {
@@ -84,7 +96,6 @@ pub fn get_input() -> ah::Result<KeyCode> {
 }
 
 /// Quality of life when invoking shell commmands.
-
 pub fn shell(command: &str, arguments: Vec<&str>) -> ah::Result<(String, String)> {
     let mut cmd = Command::new(command);
 
@@ -131,13 +142,82 @@ pub fn shell(command: &str, arguments: Vec<&str>) -> ah::Result<(String, String)
     Ok((stdout, stderr))
 }
 
+/// Runs a command with the input on its stdin, handing every line it prints
+/// to stdout to on_line as soon as it is printed, rather than once it exits.
+/// Returns the exit code, or None if the command was killed by a signal,
+/// along with whatever it printed to stderr. Fails if the command can't be
+/// run at all, or if on_line fails, in which case the command is killed.
+pub fn shell_stream(
+    command: &str,
+    arguments: Vec<&str>,
+    input: &str,
+    mut on_line: impl FnMut(&str) -> ah::Result<()>,
+) -> ah::Result<(Option<i32>, String)> {
+    let mut child = Command::new(command)
+        .args(arguments)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("shell_stream spawning command")?;
+
+    let mut child_stdin = child
+        .stdin
+        .take()
+        .ok_or(ah::anyhow!("Failed to take stdin from child process"))?;
+
+    let child_stdout: ChildStdout = child
+        .stdout
+        .take()
+        .ok_or(ah::anyhow!("Failed to take stdout from child process"))?;
+
+    let mut child_stderr: ChildStderr = child
+        .stderr
+        .take()
+        .ok_or(ah::anyhow!("Failed to take stderr from child process"))?;
+
+    // Writing the input and reading stderr happen on threads of their own, so
+    // that a full pipe on either side can never block reading stdout.
+    let streamed = thread::scope(|scope| {
+        scope.spawn(move || {
+            // The command may exit before reading all of its input, which is
+            // its own business, e.g. grep -m 1.
+            let _ = child_stdin.write_all(input.as_bytes());
+        });
+
+        let stderr = scope.spawn(move || {
+            let mut buffer = String::new();
+            let _ = child_stderr.read_to_string(&mut buffer);
+            buffer
+        });
+
+        let mut streamed = Ok(());
+
+        for line in BufReader::new(child_stdout).lines() {
+            streamed = line
+                .context("shell_stream reading stdout")
+                .and_then(|line| on_line(&line));
+
+            if streamed.is_err() {
+                let _ = child.kill();
+                break;
+            }
+        }
+
+        streamed.map(|_| stderr.join().unwrap_or_default())
+    });
+
+    let status = child.wait().context("shell_stream waiting for command")?;
+    Ok((status.code(), streamed?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_shell() {
-        let (stdout, stderr) = shell("echo", vec!["Hello,", "world!"].into()).unwrap();
+        let (stdout, stderr) = shell("echo", vec!["Hello,", "world!"]).unwrap();
         assert_eq!(stdout, "Hello, world!\n");
         assert_eq!(stderr, "");
 
@@ -145,4 +225,22 @@ mod tests {
         assert_eq!(stdout, "");
         assert_eq!(stderr, "fatal: not a git repository: '/dev/null'\n");
     }
+
+    #[test]
+    fn test_shell_stream() {
+        let mut lines: Vec<String> = Vec::new();
+        let (code, _) = shell_stream("grep", vec!["-n", "b"], "a\nb\nab\n", |line| {
+            lines.push(line.to_string());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(code, Some(0));
+        assert_eq!(lines, vec!["2:b", "3:ab"]);
+
+        let (code, _) = shell_stream("grep", vec!["c"], "a\n", |_| Ok(())).unwrap();
+        assert_eq!(code, Some(1));
+
+        assert!(shell_stream("xiny-missing-command", vec![], "", |_| Ok(())).is_err());
+    }
 }